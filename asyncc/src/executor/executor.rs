@@ -1,7 +1,9 @@
-use core::{sync::atomic::{AtomicU32, Ordering}, future::Future};
+use core::{sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering}, future::Future, task::Waker};
 
 use alloc::boxed::Box;
-use super::{queue::*, Task, TaskRef, PRIO_LEVEL, TaskType, TaskState};
+use super::{queue::*, stats::{self, ExecutorStats, Stats}, JoinHandle, Task, TaskRef, PRIO_LEVEL, TaskType, TaskState};
+use config::EXECUTOR_CAPACITY;
+use crossbeam::atomic::AtomicCell;
 
 /// 
 #[repr(u32)]
@@ -13,6 +15,40 @@ pub enum ExecutorState {
     Running = 1 << 1,
 }
 
+/// An idle `Executor` only steals from the one which has more ready tasks than this,
+/// so the last ready task stays with the busy `Executor` which is about to fetch it.
+pub const STEAL_THRESHOLD: usize = 1;
+
+/// The offset of the run queues in `Executor`, which the hardware reads through `eptr`.
+pub const RUN_QUEUE_OFFSET: usize = 8;
+
+/// The offset of `stack_poll` in `Executor`.
+pub const STACK_POLL_OFFSET: usize = RUN_QUEUE_OFFSET + QUEUE_SIZE * PRIO_LEVEL;
+
+/// The offset of the ready bitmap in `Executor`, the hardware reads it through `eptr` to
+/// decide whether to preempt.
+///
+/// The hardware sees `state`, `priority`, `run_queue` and `stack_poll` at the offsets they
+/// had before the bitmap was added, and the bitmap right after them. The fields after the
+/// bitmap, e.g. the overflow lists of the run queues, are only used by software.
+pub const BITMAP_OFFSET: usize = STACK_POLL_OFFSET + 10 * core::mem::size_of::<usize>();
+
+const _: () = assert!(PRIO_LEVEL <= u32::BITS as usize);
+const _: () = assert!(core::mem::offset_of!(Executor, state) == 0);
+const _: () = assert!(core::mem::offset_of!(Executor, priority) == 4);
+const _: () = assert!(core::mem::offset_of!(Executor, run_queue) == RUN_QUEUE_OFFSET);
+const _: () = assert!(core::mem::offset_of!(Executor, stack_poll) == STACK_POLL_OFFSET);
+const _: () = assert!(core::mem::offset_of!(Executor, bitmap) == BITMAP_OFFSET);
+
+/// The error returned by [`Executor::try_spawn`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnError {
+    /// The `Executor` already has `capacity` ready tasks.
+    Full,
+    /// The priority is not less than `PRIO_LEVEL`.
+    InvalidPriority,
+}
+
 /// The `Executor` of `async` runtime.
 #[repr(C)]
 pub struct Executor {
@@ -27,6 +63,40 @@ pub struct Executor {
     run_queue: [Queue; PRIO_LEVEL],
     /// thread ids
     stack_poll: [usize; 10],
+    /// The bit `i` is set if `run_queue[i]` may be non-empty.
+    ///
+    /// A bit is set after enqueuing, and cleared when dequeuing from an empty queue, so a set
+    /// bit may be stale for a moment, but a non-empty queue never has its bit cleared.
+    bitmap: AtomicU32,
+    /// The tasks which don't fit in `run_queue`, kept here since they are not a part of the
+    /// layout read by the hardware.
+    overflow: [Overflow; PRIO_LEVEL],
+    /// The number of tasks in `run_queue`.
+    ready: AtomicUsize,
+    /// The number of tasks stolen from others by this `Executor`.
+    stolen: AtomicUsize,
+    /// The number of tasks stolen from this `Executor` by others.
+    lost: AtomicUsize,
+    /// The maximum number of ready tasks, beyond which spawning fails.
+    ///
+    /// Woken tasks are always accepted, so waking never fails.
+    capacity: AtomicUsize,
+    /// The statistics, see [`Executor::stats`].
+    pub(crate) stats: Stats,
+    /// The number of dequeues in a row from the highest level while lower levels wait, after
+    /// which a lower level is served, see [`Executor::set_aging`]. Zero disables aging.
+    aging_threshold: AtomicUsize,
+    /// The dequeues from the highest level since a lower level was served.
+    aging_skips: AtomicUsize,
+    /// The level which was served by aging last time.
+    aging_cursor: AtomicU32,
+    /// The number of `Task`s of this `Executor` which are not freed, see
+    /// [`Executor::task_count`].
+    pub(crate) tasks: AtomicUsize,
+    /// Whether `waker` is registered, checked before taking it when enqueuing.
+    has_waker: AtomicBool,
+    /// The waker registered by [`Executor::register_waker`].
+    waker: AtomicCell<Option<Waker>>,
 }
 
 impl Executor {
@@ -34,39 +104,266 @@ impl Executor {
     pub const fn new() -> Self {
         Self {
             state: AtomicU32::new(ExecutorState::Ready as _),
-            run_queue: [Queue::EMPTY; 8],
+            run_queue: [Queue::EMPTY; PRIO_LEVEL],
             // currents: array_init::array_init(|_| None),
             stack_poll: [usize::MAX; 10],
             priority: AtomicU32::new(u32::MAX),
+            bitmap: AtomicU32::new(0),
+            overflow: [Overflow::EMPTY; PRIO_LEVEL],
+            ready: AtomicUsize::new(0),
+            stolen: AtomicUsize::new(0),
+            lost: AtomicUsize::new(0),
+            capacity: AtomicUsize::new(EXECUTOR_CAPACITY),
+            stats: Stats::new(),
+            aging_threshold: AtomicUsize::new(0),
+            aging_skips: AtomicUsize::new(0),
+            aging_cursor: AtomicU32::new(0),
+            tasks: AtomicUsize::new(0),
+            has_waker: AtomicBool::new(false),
+            waker: AtomicCell::new(None),
         }
     }
 
+    /// Sets the maximum number of ready tasks.
+    pub fn set_capacity(&self, capacity: usize) {
+        self.capacity.store(capacity, Ordering::Relaxed);
+    }
+
+    /// The maximum number of ready tasks.
+    pub fn capacity(&self) -> usize {
+        self.capacity.load(Ordering::Relaxed)
+    }
+
+    /// Enables aging if `threshold` is not zero, so the low priorities are not starved.
+    ///
+    /// After `threshold` tasks in a row are dequeued from the highest level while lower
+    /// levels have ready tasks, the next task is dequeued from a lower level. The lower levels
+    /// take turns, so a ready task waits for at most about `threshold * PRIO_LEVEL` dequeues
+    /// per task ahead of it in its queue.
+    pub fn set_aging(&self, threshold: usize) {
+        self.aging_threshold.store(threshold, Ordering::Relaxed);
+        self.aging_skips.store(0, Ordering::Relaxed);
+    }
+
+    /// The threshold of aging, zero if it is disabled.
+    pub fn aging(&self) -> usize {
+        self.aging_threshold.load(Ordering::Relaxed)
+    }
+
     /// This will not change the priority immediately
+    ///
+    /// The priority not less than `PRIO_LEVEL` is clamped to the lowest one.
     pub fn set_priority(&self, task_ref: TaskRef, priority: u32) {
         let task = unsafe { &*task_ref.as_ptr() };
         task.update_priority(priority);
     }
 
     /// spawn a new task in `Executor`
-    pub fn spawn(&'static self, fut: Box<dyn Future<Output = i32> + 'static + Send + Sync>, priority: u32, task_type: TaskType) -> TaskRef {
+    ///
+    /// Panics if the task can't be spawned, see [`Executor::try_spawn`].
+    pub fn spawn(&'static self, fut: Box<dyn Future<Output = i32> + 'static + Send + Sync>, priority: u32, task_type: TaskType) -> JoinHandle {
+        self.try_spawn(fut, priority, task_type).expect("failed to spawn task")
+    }
+
+    /// spawn a new task in `Executor`, or fails if the `Executor` is full or the priority
+    /// is invalid.
+    pub fn try_spawn(&'static self, fut: Box<dyn Future<Output = i32> + 'static + Send + Sync>, priority: u32, task_type: TaskType) -> Result<JoinHandle, SpawnError> {
+        if priority as usize >= PRIO_LEVEL {
+            return Err(SpawnError::InvalidPriority);
+        }
+        if self.ready_count() >= self.capacity() {
+            return Err(SpawnError::Full);
+        }
         let task_ref = Task::new(&self, fut, priority, task_type);
-        self.run_queue[priority as usize].enqueue(task_ref);
+        let handle = JoinHandle::new(task_ref);
+        self.stats.on_spawn();
+        self.enqueue(task_ref, priority);
         self.priority.fetch_min(priority, Ordering::Relaxed);
-        task_ref
+        Ok(handle)
     }
 
     /// fetch task which has the highest priority
     #[inline(always)]
-    pub fn fetch(&mut self) -> Option<TaskRef> {
-        for q in &self.run_queue {
-            if let Some(task_ref) = q.dequeue() {
-                let task = unsafe { &*task_ref.as_ptr() };
-                let priority = task.priority.load(Ordering::Relaxed);
-                self.priority.store(priority, Ordering::Relaxed);
+    pub fn fetch(&self) -> Option<TaskRef> {
+        let task_ref = self.dequeue()?;
+        let task = unsafe { &*task_ref.as_ptr() };
+        let priority = task.priority.load(Ordering::Relaxed);
+        self.priority.store(priority, Ordering::Relaxed);
+        Some(task_ref)
+    }
+
+    /// Enqueues a ready task into the queue of `priority`, and marks it in the bitmap.
+    ///
+    /// The priority of a task may be stored out of range directly, so it is clamped here.
+    fn enqueue(&self, task_ref: TaskRef, priority: u32) {
+        let priority = priority.min(PRIO_LEVEL as u32 - 1);
+        let task = unsafe { &*task_ref.as_ptr() };
+        task.ready_at.store(stats::now(), Ordering::Relaxed);
+        self.stats.on_enqueue(priority);
+        self.ready.fetch_add(1, Ordering::Relaxed);
+        self.run_queue[priority as usize].enqueue(task_ref, &self.overflow[priority as usize]);
+        self.bitmap.fetch_or(1 << priority, Ordering::Release);
+        core::sync::atomic::fence(Ordering::SeqCst);
+        if self.has_waker.load(Ordering::Relaxed) {
+            self.wake_registered();
+        }
+    }
+
+    /// Registers the waker which is woken once a task is enqueued, e.g. to run the kernel
+    /// process which owns this `Executor` again. It replaces the last registered one.
+    ///
+    /// The waker is woken at once if there are ready tasks.
+    pub fn register_waker(&self, waker: &Waker) {
+        self.waker.store(Some(waker.clone()));
+        self.has_waker.store(true, Ordering::Relaxed);
+        // A task may be enqueued before `has_waker` is set.
+        core::sync::atomic::fence(Ordering::SeqCst);
+        if self.ready_bitmap() != 0 {
+            self.wake_registered();
+        }
+    }
+
+    /// Takes the registered waker and wakes it.
+    fn wake_registered(&self) {
+        if self.has_waker.swap(false, Ordering::AcqRel) {
+            if let Some(waker) = self.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Dequeues the task which has the highest priority, or of a lower level by aging.
+    ///
+    /// The highest non-empty queue is found by the lowest set bit of the bitmap. If that
+    /// queue turns out empty, its bit is cleared and the queue is checked again, because a
+    /// task may be enqueued before the bit is cleared.
+    fn dequeue(&self) -> Option<TaskRef> {
+        loop {
+            let priority = self.next_priority()?;
+            let queue = &self.run_queue[priority as usize];
+            let overflow = &self.overflow[priority as usize];
+            let task_ref = queue.dequeue(overflow).or_else(|| {
+                self.bitmap.fetch_and(!(1 << priority), Ordering::AcqRel);
+                let task_ref = queue.dequeue(overflow)?;
+                self.bitmap.fetch_or(1 << priority, Ordering::Release);
+                Some(task_ref)
+            });
+            if let Some(task_ref) = task_ref {
+                self.ready.fetch_sub(1, Ordering::Relaxed);
+                self.stats.on_dequeue(priority);
                 return Some(task_ref);
             }
         }
-        None
+    }
+
+    /// Chooses the level to dequeue from, which is the highest one unless a lower one is due
+    /// by aging.
+    fn next_priority(&self) -> Option<u32> {
+        let bitmap = self.ready_bitmap();
+        if bitmap == 0 {
+            return None;
+        }
+        let highest = bitmap.trailing_zeros();
+        let lower = bitmap & !(1 << highest);
+        let threshold = self.aging_threshold.load(Ordering::Relaxed);
+        if threshold == 0 || lower == 0 {
+            self.aging_skips.store(0, Ordering::Relaxed);
+            return Some(highest);
+        }
+        if self.aging_skips.fetch_add(1, Ordering::Relaxed) < threshold {
+            return Some(highest);
+        }
+        self.aging_skips.store(0, Ordering::Relaxed);
+        // The lower levels take turns, starting after the one served last time.
+        let cursor = self.aging_cursor.load(Ordering::Relaxed);
+        let after = lower & u32::MAX.checked_shl(cursor + 1).unwrap_or(0);
+        let priority = if after != 0 { after.trailing_zeros() } else { lower.trailing_zeros() };
+        self.aging_cursor.store(priority, Ordering::Relaxed);
+        Some(priority)
+    }
+
+    /// The number of tasks which are not freed, whether they are ready, pending or finished
+    /// with a [`JoinHandle`] left.
+    ///
+    /// The tasks refer to this `Executor`, so it must not be freed until this is zero.
+    pub fn task_count(&self) -> usize {
+        self.tasks.load(Ordering::Acquire)
+    }
+
+    /// Drops the futures of the ready tasks instead of polling them, like aborted ones, and
+    /// returns the number of them.
+    pub fn drain(&self) -> usize {
+        let mut count = 0;
+        while let Some(task_ref) = self.fetch() {
+            Task::from_ref(task_ref).finish(None);
+            self.stats.on_poll_done(true);
+            count += 1;
+        }
+        count
+    }
+
+    /// The bitmap of the priorities which have ready tasks.
+    pub fn ready_bitmap(&self) -> u32 {
+        self.bitmap.load(Ordering::Acquire)
+    }
+
+    /// The highest priority which has ready tasks, the smaller the higher.
+    pub fn highest_ready_priority(&self) -> Option<u32> {
+        let bitmap = self.ready_bitmap();
+        if bitmap == 0 {
+            None
+        } else {
+            Some(bitmap.trailing_zeros())
+        }
+    }
+
+    /// The number of ready tasks.
+    pub fn ready_count(&self) -> usize {
+        self.ready.load(Ordering::Relaxed)
+    }
+
+    /// The number of tasks stolen from others by this `Executor`.
+    pub fn stolen_count(&self) -> usize {
+        self.stolen.load(Ordering::Relaxed)
+    }
+
+    /// The number of tasks stolen from this `Executor` by others.
+    pub fn lost_count(&self) -> usize {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    /// A snapshot of the statistics.
+    pub fn stats(&self) -> ExecutorStats {
+        self.stats.snapshot(self.stolen_count(), self.lost_count())
+    }
+
+    /// Clears the statistics, except the queue depths and the steal counters.
+    pub fn reset_stats(&self) {
+        self.stats.reset();
+    }
+
+    /// Steals the task which has the highest priority from `victim`, if it has more than
+    /// [`STEAL_THRESHOLD`] ready tasks.
+    ///
+    /// The task still belongs to `victim`, so it goes back to `victim` when it is woken.
+    pub fn steal(&self, victim: &Executor) -> Option<TaskRef> {
+        if core::ptr::eq(self, victim) || victim.ready_count() <= STEAL_THRESHOLD {
+            return None;
+        }
+        let task_ref = victim.dequeue()?;
+        victim.lost.fetch_add(1, Ordering::Relaxed);
+        self.stolen.fetch_add(1, Ordering::Relaxed);
+        let task = unsafe { &*task_ref.as_ptr() };
+        self.priority.store(task.priority.load(Ordering::Relaxed), Ordering::Relaxed);
+        Some(task_ref)
+    }
+
+    /// Steals from the `Executor` which has the most ready tasks among `victims`.
+    pub fn steal_from_busiest<'a>(&self, victims: impl Iterator<Item = &'a Executor>) -> Option<TaskRef> {
+        let victim = victims
+            .filter(|victim| !core::ptr::eq(self, *victim))
+            .max_by_key(|victim| victim.ready_count())?;
+        self.steal(victim)
     }
 
 
@@ -84,8 +381,187 @@ impl Executor {
         task.state.store(TaskState::Ready as _, Ordering::Relaxed);
         let priority = task.priority.load(Ordering::Relaxed);
         self.priority.fetch_min(priority, Ordering::Relaxed);
-        self.run_queue[priority as usize].enqueue(task_ref);
+        self.stats.on_wake();
+        self.enqueue(task_ref, priority);
+    }
+
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn leaked_executor() -> &'static Executor {
+        Box::leak(Box::new(Executor::new()))
+    }
+
+    /// Spawns a task which finishes at once.
+    fn spawn_nop(executor: &'static Executor, priority: u32) -> TaskRef {
+        executor.spawn(Box::new(async { 0 }), priority, TaskType::Other).task_ref()
+    }
+
+    #[test]
+    fn steal_test() {
+        let busy = leaked_executor();
+        let idle = leaked_executor();
+        spawn_nop(busy, 3);
+        assert!(idle.steal(busy).is_none());
+        let high = spawn_nop(busy, 1);
+        spawn_nop(busy, 2);
+        let stolen = idle.steal(busy).unwrap();
+        assert!(stolen.as_ptr() == high.as_ptr());
+        assert!(busy.ready_count() == 2 && idle.stolen_count() == 1 && busy.lost_count() == 1);
+        assert!(idle.steal_from_busiest([busy, idle].into_iter()).is_some());
+        assert!(idle.steal_from_busiest([busy, idle].into_iter()).is_none());
+    }
+
+    #[test]
+    fn bitmap_test() {
+        let executor = leaked_executor();
+        assert!(executor.highest_ready_priority().is_none());
+        let low = spawn_nop(executor, PRIO_LEVEL as u32 - 1);
+        let high = spawn_nop(executor, 2);
+        assert!(executor.ready_bitmap() == (1 << 2 | 1 << (PRIO_LEVEL - 1)));
+        assert!(executor.fetch().unwrap().as_ptr() == high.as_ptr());
+        assert!(executor.fetch().unwrap().as_ptr() == low.as_ptr());
+        assert!(executor.fetch().is_none());
+        assert!(executor.ready_bitmap() == 0);
+    }
+
+    #[test]
+    fn overflow_test() {
+        let executor = leaked_executor();
+        let count = RING_SIZE * 2 + 1;
+        let tasks: Vec<_> = (0..count).map(|_| spawn_nop(executor, 0)).collect();
+        for task_ref in tasks {
+            assert!(executor.fetch().unwrap().as_ptr() == task_ref.as_ptr());
+        }
+        assert!(executor.fetch().is_none());
+        executor.set_capacity(1);
+        assert!(executor.try_spawn(Box::new(async { 0 }), 0, TaskType::Other).is_ok());
+        assert!(executor.try_spawn(Box::new(async { 0 }), 0, TaskType::Other).err() == Some(SpawnError::Full));
+        assert!(executor.try_spawn(Box::new(async { 0 }), PRIO_LEVEL as u32, TaskType::Other).err() == Some(SpawnError::InvalidPriority));
+    }
+
+    #[test]
+    fn priority_clamp_test() {
+        let executor = leaked_executor();
+        let task_ref = spawn_nop(executor, 0);
+        executor.set_priority(task_ref, u32::MAX);
+        let task = unsafe { &*task_ref.as_ptr() };
+        assert!(task.priority.load(Ordering::Relaxed) == PRIO_LEVEL as u32 - 1);
+        task.priority.store(PRIO_LEVEL as u32, Ordering::Relaxed);
+        executor.wake_task_from_ref(task_ref);
+        assert!(executor.ready_bitmap() == (1 << 0 | 1 << (PRIO_LEVEL - 1)));
+    }
+
+    #[test]
+    fn drain_test() {
+        let executor = leaked_executor();
+        let handle = executor.spawn(Box::new(async { 0 }), 0, TaskType::Other);
+        spawn_nop(executor, 1);
+        assert!(executor.task_count() == 2);
+        assert!(executor.drain() == 2 && executor.fetch().is_none());
+        // The task is kept until its `JoinHandle` is dropped.
+        assert!(handle.is_finished() && executor.task_count() == 1);
+        drop(handle);
+        assert!(executor.task_count() == 0);
+    }
+
+    #[test]
+    fn register_waker_test() {
+        struct Flag(AtomicBool);
+        impl alloc::task::Wake for Flag {
+            fn wake(self: alloc::sync::Arc<Self>) {
+                self.0.store(true, Ordering::Relaxed);
+            }
+        }
+        let executor = leaked_executor();
+        let flag = alloc::sync::Arc::new(Flag(AtomicBool::new(false)));
+        executor.register_waker(&Waker::from(flag.clone()));
+        assert!(!flag.0.load(Ordering::Relaxed));
+        spawn_nop(executor, 0);
+        assert!(flag.0.swap(false, Ordering::Relaxed));
+        // The waker is taken when it is woken.
+        spawn_nop(executor, 0);
+        assert!(!flag.0.load(Ordering::Relaxed));
+        executor.register_waker(&Waker::from(flag.clone()));
+        assert!(flag.0.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn stats_test() {
+        use core::{pin::Pin, task::{Context, Poll}};
+
+        /// Wakes itself and returns `Pending` once.
+        struct YieldOnce(bool);
+
+        impl Future for YieldOnce {
+            type Output = i32;
+
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<i32> {
+                if self.0 {
+                    return Poll::Ready(0);
+                }
+                self.0 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+
+        let executor = leaked_executor();
+        spawn_nop(executor, 1);
+        executor.spawn(Box::new(YieldOnce(false)), 3, TaskType::Other);
+        let stats = executor.stats();
+        assert!(stats.spawned == 2 && stats.depth[1] == 1 && stats.depth[3] == 1);
+        // The tasks wait in the queues for a millisecond.
+        #[cfg(feature = "stats")]
+        stats::test_driver::advance(time::Duration::from_millis(1).as_ticks());
+        while let Some(task_ref) = executor.fetch() {
+            crate::execute(task_ref);
+        }
+        let stats = executor.stats();
+        assert!(stats.polled == 3 && stats.pending == 1 && stats.finished == 2 && stats.woken == 1);
+        assert!(stats.depth.iter().all(|&depth| depth == 0));
+        #[cfg(feature = "stats")]
+        assert!(stats.max_latency_us >= 1000);
+        #[cfg(not(feature = "stats"))]
+        assert!(stats.max_latency_us == 0);
+        executor.reset_stats();
+        assert!(executor.stats().polled == 0);
     }
 
+    #[test]
+    fn aging_test() {
+        /// Fetches while a task of priority 0 is spawned before every fetch, and returns the
+        /// number of fetches until all of `waiting` are fetched, or `None` after `limit` fetches.
+        fn fetches_until_served(executor: &'static Executor, waiting: &[TaskRef], limit: usize) -> Option<usize> {
+            let mut waiting = waiting.to_vec();
+            for fetches in 1..=limit {
+                spawn_nop(executor, 0);
+                let task_ref = executor.fetch().unwrap();
+                waiting.retain(|waiting| waiting.as_ptr() != task_ref.as_ptr());
+                if waiting.is_empty() {
+                    return Some(fetches);
+                }
+            }
+            None
+        }
+
+        let starved = leaked_executor();
+        let low = spawn_nop(starved, PRIO_LEVEL as u32 - 1);
+        assert!(fetches_until_served(starved, &[low], 1000).is_none());
 
+        let threshold = 4;
+        let aged = leaked_executor();
+        aged.set_aging(threshold);
+        let waiting: Vec<_> = (1..PRIO_LEVEL as u32)
+            .flat_map(|priority| [priority, priority])
+            .map(|priority| spawn_nop(aged, priority))
+            .collect();
+        let fetches = fetches_until_served(aged, &waiting, 1000).unwrap();
+        assert!(fetches <= (threshold + 1) * waiting.len());
+    }
 }
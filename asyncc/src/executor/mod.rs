@@ -3,25 +3,31 @@
 
 mod executor;
 mod queue;
+mod stats;
 mod task;
 mod waker;
 
 pub use executor::*;
+pub use stats::ExecutorStats;
 pub use task::*;
 pub use waker::*;
 
-/// pub use priority::PRIO_LEVEL;
-pub const PRIO_LEVEL: usize = 8;
+pub use priority::PRIO_LEVEL;
 
+/// The number of priority levels, selected by the `prio-level-*` features.
 ///
+/// If more than one feature is enabled by the dependents, the most levels are kept, so every
+/// dependent can spawn with the priorities it expects.
 mod priority {
-    #[cfg(feature = "prio-level-4")]
+    #[cfg(all(feature = "prio-level-4", not(feature = "prio-level-8"), not(feature = "prio-level-16")))]
     ///
     pub const PRIO_LEVEL: usize = 4;
-    #[cfg(feature = "prio-level-8")]
+    #[cfg(all(feature = "prio-level-8", not(feature = "prio-level-16")))]
     ///
     pub const PRIO_LEVEL: usize = 8;
     #[cfg(feature = "prio-level-16")]
     ///
     pub const PRIO_LEVEL: usize = 16;
+    #[cfg(not(any(feature = "prio-level-4", feature = "prio-level-8", feature = "prio-level-16")))]
+    compile_error!("one of the `prio-level-4`, `prio-level-8` and `prio-level-16` features must be enabled");
 }
@@ -1,30 +1,64 @@
 /// This mod defines some queue in the `Executor`
+///
+/// The backend is selected by cargo feature:
+/// - default: a fixed `MpMcQueue` ring, which spills to an overflow list when it is full.
+/// - `seg-queue`: the unbounded overflow lists only, which allocate for every segment, and
+///   the rings read by the hardware stay empty.
 use crate::TaskRef;
 
-// use crossbeam::queue::SegQueue;
+use crossbeam::queue::SegQueue;
 use heapless::mpmc::MpMcQueue;
 
+/// The number of slots in the fixed ring of a [`Queue`].
+pub const RING_SIZE: usize = 128;
+
+/// The size of a [`Queue`], which is a part of the layout read by the hardware: a slot is a
+/// `TaskRef` with a sequence number, followed by the dequeue and enqueue positions.
+pub const QUEUE_SIZE: usize = (RING_SIZE + 1) * 2 * core::mem::size_of::<usize>();
+
+const _: () = assert!(core::mem::size_of::<Queue>() == QUEUE_SIZE);
+
 /// This queue stores the `TaskRef` which is ready to run.
+///
+/// The hardware reads the queues through `eptr`, so this is the bare ring whatever the
+/// backend is. The tasks which don't fit are kept in an [`Overflow`] outside of it.
 #[repr(transparent)]
-pub struct Queue(MpMcQueue<TaskRef, 128>);
+pub struct Queue(MpMcQueue<TaskRef, RING_SIZE>);
 
 impl Queue {
-    pub const EMPTY: Self = Self(MpMcQueue::new());
+    pub const EMPTY: Self = Self::new();
     ///
     #[allow(unused)]
     pub const fn new() -> Self {
         Self(MpMcQueue::new())
     }
 
-    ///
+    /// Dequeues from the ring, then from `overflow`.
     #[inline(always)]
-    pub fn dequeue(&self) -> Option<TaskRef> {
-        self.0.dequeue()
+    pub fn dequeue(&self, overflow: &Overflow) -> Option<TaskRef> {
+        self.0.dequeue().or_else(|| overflow.0.pop())
     }
 
+    /// Enqueues into the ring, or spills to `overflow` when it is full.
     ///
+    /// Once there are tasks in `overflow`, the new tasks go there too, so the tasks keep their
+    /// order. With `seg-queue`, all the tasks go to `overflow`.
     #[inline(always)]
-    pub fn enqueue(&self, task_ref: TaskRef) {
-        while self.0.enqueue(task_ref).is_err() {}
+    pub fn enqueue(&self, task_ref: TaskRef, overflow: &Overflow) {
+        if cfg!(feature = "seg-queue") || !overflow.0.is_empty() || self.0.enqueue(task_ref).is_err() {
+            overflow.0.push(task_ref);
+        }
+    }
+}
+
+/// The unbounded list of the tasks which don't fit in a [`Queue`], which is only used by
+/// software.
+pub struct Overflow(SegQueue<TaskRef>);
+
+impl Overflow {
+    pub const EMPTY: Self = Self::new();
+    ///
+    pub const fn new() -> Self {
+        Self(SegQueue::new())
     }
 }
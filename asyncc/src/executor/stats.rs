@@ -0,0 +1,162 @@
+//! The statistics of an `Executor`, for profiling the scheduler.
+//!
+//! The counters are always kept, so the `Executor` has the same layout in every build. The
+//! latency is timed with `rafos-time` only if the `stats` feature is enabled, otherwise it
+//! stays zero, since the user runtimes may have no time driver.
+
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use super::PRIO_LEVEL;
+
+/// The current timestamp in ticks, or zero without the `stats` feature.
+#[inline(always)]
+pub(crate) fn now() -> u64 {
+    #[cfg(feature = "stats")]
+    {
+        time::driver::now()
+    }
+    #[cfg(not(feature = "stats"))]
+    {
+        0
+    }
+}
+
+/// The counters updated by the `Executor` and the tasks of it.
+#[repr(C)]
+pub(crate) struct Stats {
+    spawned: AtomicUsize,
+    woken: AtomicUsize,
+    polled: AtomicUsize,
+    pending: AtomicUsize,
+    finished: AtomicUsize,
+    /// The number of tasks in each `run_queue`.
+    depth: [AtomicUsize; PRIO_LEVEL],
+    /// The maximum ticks from a task being ready to being polled.
+    max_latency: AtomicU64,
+}
+
+impl Stats {
+    pub(crate) const fn new() -> Self {
+        Self {
+            spawned: AtomicUsize::new(0),
+            woken: AtomicUsize::new(0),
+            polled: AtomicUsize::new(0),
+            pending: AtomicUsize::new(0),
+            finished: AtomicUsize::new(0),
+            depth: [const { AtomicUsize::new(0) }; PRIO_LEVEL],
+            max_latency: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn on_spawn(&self) {
+        self.spawned.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_wake(&self) {
+        self.woken.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_enqueue(&self, priority: u32) {
+        self.depth[priority as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_dequeue(&self, priority: u32) {
+        self.depth[priority as usize].fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Records a poll of the task which has been ready since `ready_at`.
+    pub(crate) fn on_poll(&self, ready_at: u64) {
+        self.polled.fetch_add(1, Ordering::Relaxed);
+        self.max_latency.fetch_max(now().saturating_sub(ready_at), Ordering::Relaxed);
+    }
+
+    /// Records the outcome of a poll.
+    pub(crate) fn on_poll_done(&self, finished: bool) {
+        if finished {
+            self.finished.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.pending.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Clears the counters, except the queue depths which reflect the tasks in the queues.
+    pub(crate) fn reset(&self) {
+        self.spawned.store(0, Ordering::Relaxed);
+        self.woken.store(0, Ordering::Relaxed);
+        self.polled.store(0, Ordering::Relaxed);
+        self.pending.store(0, Ordering::Relaxed);
+        self.finished.store(0, Ordering::Relaxed);
+        self.max_latency.store(0, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self, stolen: usize, lost: usize) -> ExecutorStats {
+        let max_latency = self.max_latency.load(Ordering::Relaxed);
+        #[cfg(feature = "stats")]
+        let max_latency_us = time::Duration::from_ticks(max_latency).as_micros();
+        #[cfg(not(feature = "stats"))]
+        let max_latency_us = max_latency;
+        ExecutorStats {
+            spawned: self.spawned.load(Ordering::Relaxed),
+            woken: self.woken.load(Ordering::Relaxed),
+            polled: self.polled.load(Ordering::Relaxed),
+            pending: self.pending.load(Ordering::Relaxed),
+            finished: self.finished.load(Ordering::Relaxed),
+            stolen,
+            lost,
+            depth: core::array::from_fn(|i| self.depth[i].load(Ordering::Relaxed)),
+            max_latency_us,
+        }
+    }
+}
+
+/// A snapshot of the statistics of an `Executor`, returned by [`crate::Executor::stats`].
+///
+/// It is `repr(C)`, so the kernel can copy it to the user.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutorStats {
+    /// The number of tasks spawned.
+    pub spawned: usize,
+    /// The number of tasks woken after they returned `Pending`.
+    pub woken: usize,
+    /// The number of polls.
+    pub polled: usize,
+    /// The number of polls which returned `Pending`.
+    pub pending: usize,
+    /// The number of polls which returned `Ready`, or found the task aborted.
+    pub finished: usize,
+    /// The number of tasks stolen from others by this `Executor`.
+    pub stolen: usize,
+    /// The number of tasks stolen from this `Executor` by others.
+    pub lost: usize,
+    /// The number of ready tasks of each priority.
+    pub depth: [usize; PRIO_LEVEL],
+    /// The maximum microseconds from a task being ready to being polled, which is zero
+    /// without the `stats` feature.
+    pub max_latency_us: u64,
+}
+
+/// The time driver of the tests, whose clock only moves when the test advances it.
+#[cfg(all(test, feature = "stats"))]
+pub(crate) mod test_driver {
+    use core::sync::atomic::{AtomicU64, Ordering};
+    use time::{driver::Driver, time_driver_impl};
+
+    struct TestDriver(AtomicU64);
+
+    impl Driver for TestDriver {
+        fn now(&self) -> u64 {
+            self.0.load(Ordering::Relaxed)
+        }
+
+        /// No test awaits timers.
+        fn set_alarm(&self, _timestamp: u64) {}
+    }
+
+    time_driver_impl!(static TIME_DRIVER: TestDriver = TestDriver(AtomicU64::new(0)));
+
+    /// Moves the clock forward by `ticks`.
+    pub(crate) fn advance(ticks: u64) {
+        TIME_DRIVER.0.fetch_add(ticks, Ordering::Relaxed);
+    }
+}
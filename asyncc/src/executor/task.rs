@@ -3,14 +3,14 @@
 
 use crate::Asyncc;
 
-use super::{executor::Executor, waker};
+use super::{executor::Executor, waker, PRIO_LEVEL};
 use alloc::{boxed::Box, sync::Arc};
 use core::{
     future::Future,
     pin::Pin,
     ptr::NonNull,
-    sync::atomic::{AtomicU32, Ordering},
-    task::{Context, Poll}
+    sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+    task::{Context, Poll, Waker}
 };
 use crossbeam::atomic::AtomicCell;
 
@@ -22,7 +22,9 @@ pub enum TaskState {
     /// 
     Running = 1 << 1,
     ///
-    Pending = 1 << 2
+    Pending = 1 << 2,
+    /// The future has been dropped, and the task will never run again.
+    Finished = 1 << 3,
 }
 
 
@@ -81,6 +83,14 @@ pub struct Task {
     pub task_type: TaskType,
     /// 
     pub fut: AtomicCell<Box<dyn Future<Output = i32> + 'static + Send + Sync>>,
+    /// The output of the future, which is taken by the [`JoinHandle`].
+    output: AtomicCell<Option<i32>>,
+    /// The task is dropped instead of polled when it runs next time.
+    aborted: AtomicBool,
+    /// The waker of the coroutine awaiting the [`JoinHandle`].
+    join_waker: AtomicCell<Option<Waker>>,
+    /// The timestamp when the task was enqueued, for the latency in the statistics.
+    pub(crate) ready_at: AtomicU64,
 }
 
 impl Task {
@@ -91,18 +101,24 @@ impl Task {
         priority: u32,
         task_type: TaskType,
     ) -> TaskRef {
+        executor.tasks.fetch_add(1, Ordering::Relaxed);
         let task = Arc::new(Self {
             executor,
             state: AtomicU32::new(TaskState::Ready as _),
             priority: AtomicU32::new(priority),
             task_type,
             fut: AtomicCell::new(fut),
+            output: AtomicCell::new(None),
+            aborted: AtomicBool::new(false),
+            join_waker: AtomicCell::new(None),
+            ready_at: AtomicU64::new(0),
         });
         task.as_ref()
     }
 
-    /// Update priority
+    /// Update priority, which is clamped to the lowest one if it is not less than `PRIO_LEVEL`.
     pub fn update_priority(&self, new_priority: u32) {
+        let new_priority = new_priority.min(PRIO_LEVEL as u32 - 1);
         self.priority.store(new_priority, Ordering::Relaxed);
     }
 
@@ -116,38 +132,145 @@ impl Task {
         let raw_ptr = task_ref.as_ptr();
         unsafe { Arc::from_raw(raw_ptr) }
     }
+
+    /// Returns true if the future has been dropped.
+    pub fn is_finished(&self) -> bool {
+        self.state.load(Ordering::Acquire) == TaskState::Finished as u32
+    }
+
+    /// Drops the future, and wakes the coroutine awaiting the [`JoinHandle`].
+    pub(crate) fn finish(&self, output: Option<i32>) {
+        self.fut.store(Box::new(core::future::pending()));
+        self.output.store(output);
+        self.state.store(TaskState::Finished as _, Ordering::Release);
+        if let Some(waker) = self.join_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        self.executor.tasks.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// The handle of a spawned task, which can be awaited for the output of the task.
+///
+/// The output is `None` if the task is aborted. Dropping the handle detaches the task, which
+/// is freed once it finishes.
+pub struct JoinHandle {
+    task: Arc<Task>,
+}
+
+impl JoinHandle {
+    pub(crate) fn new(task_ref: TaskRef) -> Self {
+        unsafe { Arc::increment_strong_count(task_ref.as_ptr()) };
+        Self { task: Task::from_ref(task_ref) }
+    }
+
+    /// The `TaskRef` of the task, which is valid until the task finishes.
+    pub fn task_ref(&self) -> TaskRef {
+        unsafe { TaskRef::from_ptr(Arc::as_ptr(&self.task)) }
+    }
+
+    /// Returns true if the task has finished or been aborted.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Aborts the task, whose future is dropped the next time it runs instead of polled.
+    ///
+    /// The futures which record the `TaskRef` to wake must forget it when dropped.
+    pub fn abort(&self) {
+        if !self.task.aborted.swap(true, Ordering::AcqRel) {
+            wake_task(self.task_ref());
+        }
+    }
+}
+
+impl Future for JoinHandle {
+    type Output = Option<i32>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if !self.task.is_finished() {
+            self.task.join_waker.store(Some(cx.waker().clone()));
+            // The task may finish before the waker is stored.
+            if !self.task.is_finished() {
+                return Poll::Pending;
+            }
+        }
+        Poll::Ready(self.task.output.take())
+    }
 }
 
 /// Wake a task by `TaskRef`.
 ///
+/// The task is enqueued only if it is pending, so it is never enqueued twice. If it is
+/// running, it is marked ready, and [`execute`] enqueues it after polling.
+///
 /// You can obtain a `TaskRef` from a `Waker` using [`task_from_waker`].
 pub fn wake_task(task_ref: TaskRef) {
-    unsafe {
-        let task_ptr = task_ref.as_ptr();
-        let executor = (*task_ptr).executor;
-        executor.wake_task_from_ref(task_ref)
+    let task = unsafe { &*task_ref.as_ptr() };
+    let ready = TaskState::Ready as u32;
+    let mut state = task.state.load(Ordering::Acquire);
+    loop {
+        if state != TaskState::Pending as u32 && state != TaskState::Running as u32 {
+            return;
+        }
+        match task.state.compare_exchange(state, ready, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => break,
+            Err(current) => state = current,
+        }
+    }
+    if state == TaskState::Pending as u32 {
+        task.executor.wake_task_from_ref(task_ref);
     }
 }
 
-/// 
+/// Polls the task, and sets the cause to `Finish` or `Await`.
+///
+/// The task owns itself until it finishes, so the `Task` is freed here once it finishes if
+/// there is no [`JoinHandle`] or `Waker` of it.
 #[inline(always)]
 pub fn execute(task_ref: TaskRef) -> Option<TaskRef> {
-    unsafe {
-        let waker = waker::from_task(task_ref);
-        let mut cx = Context::from_waker(&waker);
-        let task = Task::from_ref(task_ref);
-        task.state.store(TaskState::Running as _, Ordering::Relaxed);
-        let fut = &mut *task.fut.as_ptr();
-        let mut future = Pin::new_unchecked(fut.as_mut());
-        match future.as_mut().poll(&mut cx) {
-            Poll::Ready(_) => { 
-                Asyncc::set_cause(crate::Cause::Finish);
-                None },
-            Poll::Pending => { 
-                Asyncc::set_cause(crate::Cause::Await);
-                task.state.store(TaskState::Pending as _, Ordering::Relaxed);
-                Some(task.as_ref()) 
-            },
+    let task = Task::from_ref(task_ref);
+    let stats = &task.executor.stats;
+    if task.aborted.load(Ordering::Acquire) {
+        stats.on_poll_done(true);
+        task.finish(None);
+        Asyncc::set_cause(crate::Cause::Finish);
+        return None;
+    }
+    task.state.store(TaskState::Running as _, Ordering::Release);
+    let waker = unsafe { waker::from_task(task_ref) };
+    let mut cx = Context::from_waker(&waker);
+    let fut = unsafe { &mut *task.fut.as_ptr() };
+    let mut future = unsafe { Pin::new_unchecked(fut.as_mut()) };
+    stats.on_poll(task.ready_at.load(Ordering::Relaxed));
+    match future.as_mut().poll(&mut cx) {
+        Poll::Ready(output) => {
+            stats.on_poll_done(true);
+            drop(waker);
+            task.finish(Some(output));
+            Asyncc::set_cause(crate::Cause::Finish);
+            None
+        }
+        Poll::Pending => {
+            stats.on_poll_done(false);
+            Asyncc::set_cause(crate::Cause::Await);
+            let executor = task.executor;
+            let task_ref = task.as_ref();
+            let running = TaskState::Running as u32;
+            let pending = TaskState::Pending as u32;
+            let woken = unsafe { &*task_ref.as_ptr() }
+                .state
+                .compare_exchange(running, pending, Ordering::AcqRel, Ordering::Acquire)
+                .is_err();
+            if woken {
+                executor.wake_task_from_ref(task_ref);
+            }
+            Some(task_ref)
         }
     }
 }
@@ -4,21 +4,43 @@
 use super::task::{wake_task, Task, TaskRef};
 use core::task::{RawWaker, RawWakerVTable, Waker};
 
-const VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake, drop);
+use alloc::sync::Arc;
+
+/// Every `Waker` holds a reference count of the `Task`, so it can't outlive the `Task`.
+const VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop);
 
 unsafe fn clone(p: *const ()) -> RawWaker {
+    Arc::increment_strong_count(p as *const Task);
     RawWaker::new(p, &VTABLE)
 }
 
 unsafe fn wake(p: *const ()) {
+    wake_by_ref(p);
+    drop(p);
+}
+
+unsafe fn wake_by_ref(p: *const ()) {
     wake_task(TaskRef::from_ptr(p as *const Task))
 }
 
-unsafe fn drop(_p: *const ()) {
-    // nop
+unsafe fn drop(p: *const ()) {
+    Arc::decrement_strong_count(p as *const Task);
+}
+
+/// Gets the `TaskRef` of coroutine from its `Waker`.
+///
+/// # Panics
+/// Panics if the `Waker` is not created by [`from_task`].
+pub fn task_from_waker(waker: &Waker) -> TaskRef {
+    assert!(waker.vtable() == &VTABLE, "Found waker not created by the asyncc executor");
+    unsafe { TaskRef::from_ptr(waker.data() as *const Task) }
 }
 
-/// 
+/// Creates a `Waker` of the task, which holds a reference count of it.
+///
+/// # Safety
+/// The task must not be freed.
 pub unsafe fn from_task(task_ref: TaskRef) -> Waker {
+    Arc::increment_strong_count(task_ref.as_ptr());
     Waker::from_raw(RawWaker::new(task_ref.as_ptr() as _, &VTABLE))
 }
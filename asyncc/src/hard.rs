@@ -0,0 +1,109 @@
+//! The registers of the Asyncc controller, mapped at `ASYNCC_ADDR`.
+//!
+//! The registers are 32 bits wide, and each argument is split into the LSB and MSB halves.
+
+use config::ASYNCC_ADDR;
+
+#[inline(always)]
+fn hardware() -> &'static asyncc_pac::asyncc::RegisterBlock {
+    unsafe { &*(ASYNCC_ADDR as *const _) }
+}
+
+#[inline(always)]
+pub(crate) fn eptr() -> usize {
+    hardware().eptr().read().bits() as _
+}
+
+#[inline(always)]
+pub(crate) fn set_eptr(eptr: usize) {
+    hardware().eptr().write(|w| unsafe { w.bits(eptr as _) });
+}
+
+#[inline(always)]
+pub(crate) fn status() -> u32 {
+    hardware().status().read().bits()
+}
+
+#[inline(always)]
+pub(crate) fn set_status(status: u32) {
+    hardware().status().write(|w| unsafe { w.bits(status) });
+}
+
+#[inline(always)]
+pub(crate) fn msgbuf() -> usize {
+    hardware().msgbuf().read().bits() as _
+}
+
+#[inline(always)]
+pub(crate) fn set_msgbuf(msgbuf: usize) {
+    hardware().msgbuf().write(|w| unsafe { w.bits(msgbuf as _) });
+}
+
+#[inline(always)]
+pub(crate) fn curc() -> usize {
+    hardware().curc().read().bits() as _
+}
+
+#[inline(always)]
+pub(crate) fn set_curc(curc: usize) {
+    hardware().curc().write(|w| unsafe { w.bits(curc as _) });
+}
+
+/// Reads the argument register `a{index}`.
+pub(crate) fn arg(index: usize) -> usize {
+    let hardware = hardware();
+    let (lsb, msb) = match index {
+        0 => (hardware.a0_lsb().read().bits(), hardware.a0_msb().read().bits()),
+        1 => (hardware.a1_lsb().read().bits(), hardware.a1_msb().read().bits()),
+        2 => (hardware.a2_lsb().read().bits(), hardware.a2_msb().read().bits()),
+        3 => (hardware.a3_lsb().read().bits(), hardware.a3_msb().read().bits()),
+        4 => (hardware.a4_lsb().read().bits(), hardware.a4_msb().read().bits()),
+        5 => (hardware.a5_lsb().read().bits(), hardware.a5_msb().read().bits()),
+        6 => (hardware.a6_lsb().read().bits(), hardware.a6_msb().read().bits()),
+        7 => (hardware.a7_lsb().read().bits(), hardware.a7_msb().read().bits()),
+        _ => panic!("invalid argument register a{}", index),
+    };
+    lsb as usize | (msb as usize) << 32
+}
+
+/// Writes the argument register `a{index}`.
+pub(crate) fn set_arg(index: usize, value: usize) {
+    let hardware = hardware();
+    let lsb = value as u32;
+    let msb = (value >> 32) as u32;
+    match index {
+        0 => {
+            hardware.a0_lsb().write(|w| unsafe { w.bits(lsb) });
+            hardware.a0_msb().write(|w| unsafe { w.bits(msb) });
+        }
+        1 => {
+            hardware.a1_lsb().write(|w| unsafe { w.bits(lsb) });
+            hardware.a1_msb().write(|w| unsafe { w.bits(msb) });
+        }
+        2 => {
+            hardware.a2_lsb().write(|w| unsafe { w.bits(lsb) });
+            hardware.a2_msb().write(|w| unsafe { w.bits(msb) });
+        }
+        3 => {
+            hardware.a3_lsb().write(|w| unsafe { w.bits(lsb) });
+            hardware.a3_msb().write(|w| unsafe { w.bits(msb) });
+        }
+        4 => {
+            hardware.a4_lsb().write(|w| unsafe { w.bits(lsb) });
+            hardware.a4_msb().write(|w| unsafe { w.bits(msb) });
+        }
+        5 => {
+            hardware.a5_lsb().write(|w| unsafe { w.bits(lsb) });
+            hardware.a5_msb().write(|w| unsafe { w.bits(msb) });
+        }
+        6 => {
+            hardware.a6_lsb().write(|w| unsafe { w.bits(lsb) });
+            hardware.a6_msb().write(|w| unsafe { w.bits(msb) });
+        }
+        7 => {
+            hardware.a7_lsb().write(|w| unsafe { w.bits(lsb) });
+            hardware.a7_msb().write(|w| unsafe { w.bits(msb) });
+        }
+        _ => panic!("invalid argument register a{}", index),
+    }
+}
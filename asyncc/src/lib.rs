@@ -1,5 +1,9 @@
+//! The coroutine runtime shared by the kernel, the user runtime and the user apps, so they
+//! have one task ABI.
 //!
-//! 
+//! The backends are selected by cargo feature:
+//! - `soft`: keep the registers of the Asyncc controller in memory instead of the hardware.
+//! - `seg-queue`: keep the ready tasks in unbounded `SegQueue`s instead of fixed rings.
 #![cfg_attr(not(test), no_std)]
 #![deny(missing_docs)]
 
@@ -9,6 +13,15 @@ mod executor;
 mod cause;
 mod queue;
 mod args;
+#[cfg(not(any(test, feature = "soft")))]
+mod hard;
+#[cfg(any(test, feature = "soft"))]
+mod soft;
+
+#[cfg(not(any(test, feature = "soft")))]
+use hard as regs;
+#[cfg(any(test, feature = "soft"))]
+use soft as regs;
 
 pub use executor::*;
 pub use cause::*;
@@ -18,8 +31,6 @@ pub use args::*;
 use alloc::boxed::Box;
 use core::future::Future;
 
-use config::ASYNCC_ADDR;
-
 /// 
 #[derive(Debug)]
 pub struct Asyncc;
@@ -27,27 +38,31 @@ pub struct Asyncc;
 
 impl Asyncc {
 
-    ///
-    #[inline]
-    fn hardware() -> &'static asyncc_pac::asyncc::RegisterBlock {
-        unsafe { &*(ASYNCC_ADDR as *const _) }
+    /// The mode of execution flow change in the status register.
+    #[inline(always)]
+    fn mode() -> u32 {
+        regs::status() >> 30
     }
 
     ///
     #[inline(always)]
     pub fn get_executor() -> &'static mut Executor {
-        
-        let hardware = Self::hardware();
-        let executor_ptr = hardware.eptr().read().bits() as *const usize as *mut Executor;
+        let executor_ptr = regs::eptr() as *mut Executor;
         unsafe { &mut *executor_ptr }
     }
 
     ///
-    pub fn spawn(fut: Box<dyn Future<Output = i32> + 'static + Send + Sync>, priority: u32, task_type: TaskType) -> TaskRef {
+    pub fn spawn(fut: Box<dyn Future<Output = i32> + 'static + Send + Sync>, priority: u32, task_type: TaskType) -> JoinHandle {
         let executor = Self::get_executor();
         executor.spawn(fut, priority, task_type)
     }
 
+    /// Spawns a task, or fails if the `Executor` is full or the priority is invalid.
+    pub fn try_spawn(fut: Box<dyn Future<Output = i32> + 'static + Send + Sync>, priority: u32, task_type: TaskType) -> Result<JoinHandle, SpawnError> {
+        let executor = Self::get_executor();
+        executor.try_spawn(fut, priority, task_type)
+    }
+
     ///
     pub fn spawn_task(task_ref: TaskRef) {
         let executor = Self::get_executor();
@@ -56,109 +71,116 @@ impl Asyncc {
 
     /// init
     pub fn reset(executor: *const Executor) {
-        Self::hardware().eptr().write(|w| unsafe { w.bits(executor as *const u32 as _) });
+        regs::set_eptr(executor as usize);
     }
 
     /// 
     pub fn is_finished() -> bool {
-        Self::hardware().status().read().mode().is_finish()
+        Self::mode() == 0
     }
 
     /// 
     pub fn is_await() -> bool {
-        Self::hardware().status().read().mode().is_await()
+        Self::mode() == 1
     }
 
     /// 
     pub fn is_exception() -> bool {
-        Self::hardware().status().read().mode().is_exception()
+        Self::mode() == 2
     }
 
     /// 
     pub fn is_interrupt() -> bool {
-        Self::hardware().status().read().mode().is_interrupt()
+        Self::mode() == 3
     }
 
 
     /// 
     #[inline(always)]
     pub fn cause() -> Cause {
-        Self::hardware().status().read().bits().into()
+        regs::status().into()
     }
 
     /// 
     pub fn set_cause(cause: Cause) {
-        Self::hardware().status().write(|w| unsafe { w.bits(cause.into()) });
+        regs::set_status(cause.into());
     }
 
     /// 
     pub fn set_msgbuf(msgbuf: usize) {
-        Self::hardware().msgbuf().write(|w| unsafe { w.bits(msgbuf as _) });
+        regs::set_msgbuf(msgbuf);
     }
 
     ///
     pub fn get_msgqueue() -> &'static MsgQueue {
-        let queue_ptr = Self::hardware().msgbuf().read().bits() as *const usize as *const MsgQueue;
+        let queue_ptr = regs::msgbuf() as *const MsgQueue;
         unsafe { &*queue_ptr }
     }
 
+    /// Dispatches the messages in the [`MsgBuf`] to the coroutines awaiting them, returns the
+    /// number of messages.
+    pub fn dispatch_messages() -> usize {
+        if regs::msgbuf() == 0 {
+            return 0;
+        }
+        queue::dispatch(Self::get_msgqueue())
+    }
+
     ///
     pub fn set_curr(task_ref: Option<TaskRef>) {
-        let curc = Self::hardware().curc();
-        if let Some(task_ref) = task_ref {
-            curc.write(|w| unsafe { w.bits(task_ref.as_ptr() as *const u32 as _) });
-        } else {
-            curc.write(|w| unsafe { w.bits(0) });
-        }
+        regs::set_curc(task_ref.map_or(0, |task_ref| task_ref.as_ptr() as usize));
     }
 
     ///
     pub fn get_curr() -> Option<TaskRef> {
-        let task_raw_ptr = Self::hardware().curc().read().bits();
+        let task_raw_ptr = regs::curc();
         if task_raw_ptr == 0 {
             return None;
         }
-        unsafe { Some(TaskRef::from_ptr(task_raw_ptr as *const u32 as *const Task)) }
+        unsafe { Some(TaskRef::from_ptr(task_raw_ptr as *const Task)) }
     }
 
     ///
     pub fn set_args(a0: usize) {
-        let hardware = Self::hardware();
-        hardware.a0_lsb().write(|w| unsafe { w.bits(a0 as u32 & 0xFFFFFFFF) });
-        hardware.a0_msb().write(|w| unsafe { w.bits((a0 >> 32) as u32 & 0xFFFFFFFF) });
+        regs::set_arg(0, a0);
     }
 
     ///
     pub fn set_args2(a0: usize, a1: usize) {
-        let hardware = Self::hardware();
-        hardware.a0_lsb().write(|w| unsafe { w.bits(a0 as u32 & 0xFFFFFFFF) });
-        hardware.a0_msb().write(|w| unsafe { w.bits((a0 >> 32) as u32 & 0xFFFFFFFF) });
-        hardware.a1_lsb().write(|w| unsafe { w.bits(a1 as u32 & 0xFFFFFFFF) });
-        hardware.a1_msb().write(|w| unsafe { w.bits((a1 >> 32) as u32 & 0xFFFFFFFF) });
+        regs::set_arg(0, a0);
+        regs::set_arg(1, a1);
+    }
+
+    /// Sets `a0..a2`.
+    pub fn set_args3(a0: usize, a1: usize, a2: usize) {
+        regs::set_arg(0, a0);
+        regs::set_arg(1, a1);
+        regs::set_arg(2, a2);
+    }
+
+    /// Sets `a0..a3`, e.g. the token, `Executor`, stack and `MsgBuf` of the process to switch
+    /// to.
+    pub fn set_args4(a0: usize, a1: usize, a2: usize, a3: usize) {
+        Self::set_args3(a0, a1, a2);
+        regs::set_arg(3, a3);
     }
 
     ///
     pub fn get_args() -> Args {
-        let mut _a0 = 0usize;
-        let hardware = Self::hardware();
-        _a0 = hardware.a0_lsb().read().bits() as _;
-        _a0 |= (hardware.a0_msb().read().bits() as usize) << 32;
-        Args { a: [_a0, 0, 0, 0, 0, 0, 0, 0] }
+        Args { a: [regs::arg(0), 0, 0, 0, 0, 0, 0, 0] }
     }
 
     ///
     pub fn get_args2() -> Args {
-        let mut _a0 = 0usize;
-        let mut _a1 = 0usize;
-        let hardware = Self::hardware();
-        _a0 = hardware.a0_lsb().read().bits() as _;
-        _a0 |= (hardware.a0_msb().read().bits() as usize) << 32;
-        _a1 = hardware.a1_lsb().read().bits() as _;
-        _a1 |= (hardware.a1_msb().read().bits() as usize) << 32;
-        Args { a: [_a0, _a1, 0, 0, 0, 0, 0, 0] }
+        Args { a: [regs::arg(0), regs::arg(1), 0, 0, 0, 0, 0, 0] }
     }
 
-
-
-
+    /// Reads all the argument registers, e.g. `a7` and `a0..a5` of a syscall.
+    pub fn get_args8() -> Args {
+        let mut a = [0; 8];
+        for (i, arg) in a.iter_mut().enumerate() {
+            *arg = regs::arg(i);
+        }
+        Args { a }
+    }
 }
\ No newline at end of file
@@ -1,8 +1,247 @@
 /// this mod define the message buffer
-/// 
+///
+/// A process registers its [`MsgBuf`] to the kernel, then other processes or the kernel
+/// enqueue [`Message`]s into it directly, without switching to the process. The user
+/// `Executor` calls [`crate::Asyncc::dispatch_messages`] when it is idle, which wakes the
+/// coroutines awaiting [`recv_message`] with the keys of the messages.
 
-use heapless::mpmc::MpMcQueue;
+use alloc::collections::{BTreeMap, VecDeque};
+use core::{
+    cell::UnsafeCell,
+    future::Future,
+    mem::MaybeUninit,
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+};
+use spin::Mutex;
 
-use crate::TaskRef;
-/// 
-pub type MsgQueue = MpMcQueue<TaskRef, 128>;
\ No newline at end of file
+use crate::{Task, TaskRef};
+
+/// The maximum bytes of the payload in a [`Message`].
+pub const MSG_PAYLOAD_SIZE: usize = 24;
+
+/// The number of messages a [`MsgBuf`] holds.
+pub const MSGQUEUE_SIZE: usize = 64;
+
+/// The key of the mailbox, to which `MailWrite` posts.
+pub const MAILBOX_KEY: usize = 0;
+
+/// The key of the messages from the kernel which resume a task, see [`Message::resume`].
+pub const RESUME_KEY: usize = usize::MAX;
+
+/// A wakeup, or a small payload, posted to a process.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Message {
+    /// The pid of the sender, 0 for the kernel.
+    pub sender: usize,
+    /// Selects the coroutine to wake in the receiver, see [`recv_message`].
+    pub key: usize,
+    /// The number of bytes in `payload`.
+    pub len: usize,
+    /// The bytes carried by the message.
+    pub payload: [u8; MSG_PAYLOAD_SIZE],
+}
+
+impl Message {
+    /// Creates a message carrying `data`, which is truncated to [`MSG_PAYLOAD_SIZE`] bytes.
+    pub fn new(sender: usize, key: usize, data: &[u8]) -> Self {
+        let len = data.len().min(MSG_PAYLOAD_SIZE);
+        let mut payload = [0; MSG_PAYLOAD_SIZE];
+        payload[..len].copy_from_slice(&data[..len]);
+        Self { sender, key, len, payload }
+    }
+
+    /// Creates a message from the kernel which puts the task back to the run queue.
+    ///
+    /// It is used by `fork`, since the child has a copy of the task which was being polled.
+    pub fn resume(task_ref: TaskRef) -> Self {
+        Self::new(0, RESUME_KEY, &(task_ref.as_ptr() as usize).to_ne_bytes())
+    }
+
+    /// The bytes of the payload.
+    pub fn data(&self) -> &[u8] {
+        &self.payload[..self.len.min(MSG_PAYLOAD_SIZE)]
+    }
+}
+
+/// The times [`MsgQueue::enqueue`] retries before giving up.
+///
+/// The kernel enqueues into a page which the user can write, so it must not spin on whatever
+/// the user leaves there.
+const ENQUEUE_RETRIES: usize = 16;
+
+const _: () = assert!(MSGQUEUE_SIZE.is_power_of_two());
+
+/// A slot of [`MsgQueue`], which is ready for the enqueue at `sequence`, or for the dequeue
+/// at `sequence - 1`.
+#[repr(C)]
+struct MsgSlot {
+    sequence: AtomicUsize,
+    message: UnsafeCell<MaybeUninit<Message>>,
+}
+
+/// The bounded MPMC queue of messages in a [`MsgBuf`].
+#[repr(C)]
+pub struct MsgQueue {
+    enqueue_pos: AtomicUsize,
+    dequeue_pos: AtomicUsize,
+    slots: [MsgSlot; MSGQUEUE_SIZE],
+}
+
+unsafe impl Sync for MsgQueue {}
+
+impl MsgQueue {
+    /// Creates an empty queue.
+    pub const fn new() -> Self {
+        let mut slots = [const { MsgSlot { sequence: AtomicUsize::new(0), message: UnsafeCell::new(MaybeUninit::uninit()) } }; MSGQUEUE_SIZE];
+        let mut i = 0;
+        while i < MSGQUEUE_SIZE {
+            slots[i].sequence = AtomicUsize::new(i);
+            i += 1;
+        }
+        Self { enqueue_pos: AtomicUsize::new(0), dequeue_pos: AtomicUsize::new(0), slots }
+    }
+
+    /// Enqueues the message, which is given back if the queue is full, or if it is still
+    /// contended after [`ENQUEUE_RETRIES`] tries.
+    pub fn enqueue(&self, message: Message) -> Result<(), Message> {
+        let mut pos = self.enqueue_pos.load(Ordering::Relaxed);
+        for _ in 0..ENQUEUE_RETRIES {
+            let slot = &self.slots[pos % MSGQUEUE_SIZE];
+            match slot.sequence.load(Ordering::Acquire).wrapping_sub(pos) as isize {
+                0 => match self.enqueue_pos.compare_exchange_weak(pos, pos.wrapping_add(1), Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => {
+                        unsafe { (*slot.message.get()).write(message) };
+                        slot.sequence.store(pos.wrapping_add(1), Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => pos = current,
+                },
+                dif if dif < 0 => return Err(message),
+                _ => pos = self.enqueue_pos.load(Ordering::Relaxed),
+            }
+        }
+        Err(message)
+    }
+
+    /// Dequeues the earliest message.
+    pub fn dequeue(&self) -> Option<Message> {
+        let mut pos = self.dequeue_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos % MSGQUEUE_SIZE];
+            match slot.sequence.load(Ordering::Acquire).wrapping_sub(pos.wrapping_add(1)) as isize {
+                0 => match self.dequeue_pos.compare_exchange_weak(pos, pos.wrapping_add(1), Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => {
+                        let message = unsafe { (*slot.message.get()).assume_init_read() };
+                        slot.sequence.store(pos.wrapping_add(MSGQUEUE_SIZE), Ordering::Release);
+                        return Some(message);
+                    }
+                    Err(current) => pos = current,
+                },
+                dif if dif < 0 => return None,
+                _ => pos = self.dequeue_pos.load(Ordering::Relaxed),
+            }
+        }
+    }
+}
+
+impl Default for MsgQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The message buffer of a process, which takes exactly one page, so the kernel can write it
+/// through the physical frame.
+#[repr(C, align(4096))]
+pub struct MsgBuf {
+    /// The queue which the register `msgbuf` points to.
+    pub queue: MsgQueue,
+}
+
+const _: () = assert!(core::mem::size_of::<MsgBuf>() == 4096);
+
+impl MsgBuf {
+    /// Creates an empty message buffer.
+    pub const fn new() -> Self {
+        Self { queue: MsgQueue::new() }
+    }
+}
+
+impl Default for MsgBuf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The messages of a key which are not received yet, and the coroutine awaiting them.
+#[derive(Default)]
+struct Slot {
+    messages: VecDeque<Message>,
+    waker: Option<Waker>,
+}
+
+static SLOTS: Mutex<BTreeMap<usize, Slot>> = Mutex::new(BTreeMap::new());
+
+/// Moves the messages out of `queue` to their keys, and wakes the coroutines awaiting them.
+///
+/// The messages of [`RESUME_KEY`] from the kernel put their tasks back to the run queue.
+///
+/// Returns the number of messages.
+pub(crate) fn dispatch(queue: &MsgQueue) -> usize {
+    let mut count = 0;
+    while let Some(message) = queue.dequeue() {
+        count += 1;
+        if let (0, RESUME_KEY, Ok(ptr)) = (message.sender, message.key, message.data().try_into()) {
+            let task_ref = unsafe { TaskRef::from_ptr(usize::from_ne_bytes(ptr) as *const Task) };
+            let task = unsafe { &*task_ref.as_ptr() };
+            task.executor.wake_task_from_ref(task_ref);
+            continue;
+        }
+        let waker = {
+            let mut slots = SLOTS.lock();
+            let slot = slots.entry(message.key).or_default();
+            slot.messages.push_back(message);
+            slot.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+    count
+}
+
+/// Receives a message of `key`, the messages are kept until received.
+///
+/// A key is awaited by one coroutine at a time, the last one to poll is woken.
+pub fn recv_message(key: usize) -> RecvMessage {
+    RecvMessage { key }
+}
+
+/// The future returned by [`recv_message`].
+pub struct RecvMessage {
+    key: usize,
+}
+
+impl Future for RecvMessage {
+    type Output = Message;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slots = SLOTS.lock();
+        let slot = slots.entry(self.key).or_default();
+        match slot.messages.pop_front() {
+            Some(message) => {
+                if slot.messages.is_empty() && slot.waker.is_none() {
+                    slots.remove(&self.key);
+                }
+                Poll::Ready(message)
+            }
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,340 @@
+//! A software model of the Asyncc controller, whose registers are kept in memory.
+//!
+//! It is enabled by the `soft` feature or in `cargo test`, so the `Executor`, [`execute`]
+//! and the Finish/Await flow of the handler can run on the host. The registers are banked
+//! per hart in hardware, and per thread in tests, so every thread models a hart. Unlike the
+//! hardware, the registers are as wide as `usize`, so host pointers fit in them.
+//!
+//! [`execute`]: crate::execute
+
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+/// The in-memory registers of a hart.
+struct Registers {
+    eptr: AtomicUsize,
+    status: AtomicU32,
+    msgbuf: AtomicUsize,
+    curc: AtomicUsize,
+    args: [AtomicUsize; 8],
+}
+
+impl Registers {
+    const fn new() -> Self {
+        Self {
+            eptr: AtomicUsize::new(0),
+            status: AtomicU32::new(0),
+            msgbuf: AtomicUsize::new(0),
+            curc: AtomicUsize::new(0),
+            args: [const { AtomicUsize::new(0) }; 8],
+        }
+    }
+}
+
+#[cfg(test)]
+std::thread_local! {
+    static REGISTERS: Registers = const { Registers::new() };
+}
+
+/// The registers of the current thread.
+#[cfg(test)]
+fn registers() -> &'static Registers {
+    // The registers live as long as the thread, which outlives any use of them.
+    REGISTERS.with(|registers| unsafe { &*(registers as *const Registers) })
+}
+
+#[cfg(not(test))]
+static REGISTERS: Registers = Registers::new();
+
+/// The registers of the only hart.
+#[cfg(not(test))]
+fn registers() -> &'static Registers {
+    &REGISTERS
+}
+
+pub(crate) fn eptr() -> usize {
+    registers().eptr.load(Ordering::Relaxed)
+}
+
+pub(crate) fn set_eptr(eptr: usize) {
+    registers().eptr.store(eptr, Ordering::Relaxed);
+}
+
+pub(crate) fn status() -> u32 {
+    registers().status.load(Ordering::Relaxed)
+}
+
+pub(crate) fn set_status(status: u32) {
+    registers().status.store(status, Ordering::Relaxed);
+}
+
+pub(crate) fn msgbuf() -> usize {
+    registers().msgbuf.load(Ordering::Relaxed)
+}
+
+pub(crate) fn set_msgbuf(msgbuf: usize) {
+    registers().msgbuf.store(msgbuf, Ordering::Relaxed);
+}
+
+pub(crate) fn curc() -> usize {
+    registers().curc.load(Ordering::Relaxed)
+}
+
+pub(crate) fn set_curc(curc: usize) {
+    registers().curc.store(curc, Ordering::Relaxed);
+}
+
+/// Reads the argument register `a{index}`.
+pub(crate) fn arg(index: usize) -> usize {
+    registers().args[index].load(Ordering::Relaxed)
+}
+
+/// Writes the argument register `a{index}`.
+pub(crate) fn set_arg(index: usize, value: usize) {
+    registers().args[index].store(value, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{execute, recv_message, Asyncc, Cause, Executor, Message, MsgBuf, TaskType, MSGQUEUE_SIZE, PRIO_LEVEL};
+    use alloc::{boxed::Box, sync::Arc, vec::Vec};
+    use core::{
+        future::Future,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+    use std::sync::Mutex;
+
+    /// Returns `Pending` `count` times, waking itself each time.
+    struct Yield(usize);
+
+    impl Future for Yield {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            if self.0 == 0 {
+                return Poll::Ready(());
+            }
+            self.0 -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    /// Runs the tasks in the `Executor` of Asyncc like the trampoline handler does, and
+    /// returns the numbers of Finish and Await.
+    fn run() -> (usize, usize) {
+        let (mut finished, mut awaited) = (0, 0);
+        while let Some(task_ref) = Asyncc::get_executor().fetch() {
+            Asyncc::set_curr(Some(task_ref));
+            execute(task_ref);
+            Asyncc::set_curr(None);
+            match Asyncc::cause() {
+                Cause::Finish => finished += 1,
+                Cause::Await => awaited += 1,
+                cause => panic!("unexpected cause {:?}", cause),
+            }
+        }
+        (finished, awaited)
+    }
+
+    #[test]
+    fn registers_test() {
+        Asyncc::set_args2(usize::MAX, 0x1234_5678_9abc);
+        assert!(Asyncc::get_args2().a[..2] == [usize::MAX, 0x1234_5678_9abc]);
+        Asyncc::set_args3(1, 2, 3);
+        assert!(Asyncc::get_args8().a[..3] == [1, 2, 3]);
+        Asyncc::set_args4(4, 5, 6, 7);
+        assert!(Asyncc::get_args8().a[..4] == [4, 5, 6, 7]);
+        Asyncc::set_cause(Cause::Await);
+        assert!(Asyncc::is_await() && !Asyncc::is_finished());
+        assert!(Asyncc::get_curr().is_none());
+        // Every thread models a hart with its own registers.
+        std::thread::spawn(|| assert!(Asyncc::get_args().a[0] == 0)).join().unwrap();
+    }
+
+    #[test]
+    fn handler_test() {
+        let executor: &'static Executor = Box::leak(Box::new(Executor::new()));
+        Asyncc::reset(executor);
+        for yields in 0..4 {
+            Asyncc::spawn(
+                Box::new(async move {
+                    Yield(yields).await;
+                    0
+                }),
+                1,
+                TaskType::Other,
+            );
+        }
+        assert!(run() == (4, 1 + 2 + 3));
+    }
+
+    #[test]
+    fn priority_order_test() {
+        let executor: &'static Executor = Box::leak(Box::new(Executor::new()));
+        Asyncc::reset(executor);
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut seed = 0x2545_f491_u32;
+        for _ in 0..64 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let priority = (seed >> 16) % PRIO_LEVEL as u32;
+            let order = order.clone();
+            Asyncc::spawn(
+                Box::new(async move {
+                    order.lock().unwrap().push(priority);
+                    0
+                }),
+                priority,
+                TaskType::Other,
+            );
+        }
+        assert!(run() == (64, 0));
+        let order = order.lock().unwrap();
+        assert!(order.len() == 64 && order.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    /// Sets the flag when dropped.
+    struct DropFlag(Arc<Mutex<bool>>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            *self.0.lock().unwrap() = true;
+        }
+    }
+
+    #[test]
+    fn join_test() {
+        let executor: &'static Executor = Box::leak(Box::new(Executor::new()));
+        Asyncc::reset(executor);
+        let output = Arc::new(Mutex::new(None));
+        let parent_output = output.clone();
+        Asyncc::spawn(
+            Box::new(async move {
+                let child = Asyncc::spawn(
+                    Box::new(async {
+                        Yield(2).await;
+                        42
+                    }),
+                    2,
+                    TaskType::Other,
+                );
+                *parent_output.lock().unwrap() = child.await;
+                0
+            }),
+            1,
+            TaskType::Other,
+        );
+        // The parent awaits once for the child, which yields twice.
+        assert!(run() == (2, 3));
+        assert!(*output.lock().unwrap() == Some(42));
+    }
+
+    #[test]
+    fn abort_test() {
+        let executor: &'static Executor = Box::leak(Box::new(Executor::new()));
+        Asyncc::reset(executor);
+        let dropped = Arc::new(Mutex::new(false));
+        let flag = DropFlag(dropped.clone());
+        let handle = Asyncc::spawn(
+            Box::new(async move {
+                let _flag = flag;
+                core::future::pending::<()>().await;
+                0
+            }),
+            0,
+            TaskType::Other,
+        );
+        assert!(run() == (0, 1));
+        assert!(!*dropped.lock().unwrap() && !handle.is_finished());
+        handle.abort();
+        assert!(run() == (1, 0));
+        assert!(*dropped.lock().unwrap() && handle.is_finished());
+        let mut handle = Box::pin(handle);
+        let waker = std::task::Waker::noop();
+        let mut cx = Context::from_waker(waker);
+        assert!(handle.as_mut().poll(&mut cx) == Poll::Ready(None));
+    }
+
+    #[test]
+    fn detach_test() {
+        let executor: &'static Executor = Box::leak(Box::new(Executor::new()));
+        Asyncc::reset(executor);
+        let shared = Arc::new(());
+        let captured = shared.clone();
+        drop(Asyncc::spawn(
+            Box::new(async move {
+                Yield(1).await;
+                let _captured = captured;
+                0
+            }),
+            0,
+            TaskType::Other,
+        ));
+        assert!(run() == (1, 1));
+        // The future is dropped with the `Task` once it finishes.
+        assert!(Arc::strong_count(&shared) == 1);
+    }
+
+    #[test]
+    fn message_test() {
+        let executor: &'static Executor = Box::leak(Box::new(Executor::new()));
+        let msgbuf: &'static MsgBuf = Box::leak(Box::new(MsgBuf::new()));
+        Asyncc::reset(executor);
+        Asyncc::set_msgbuf(msgbuf as *const MsgBuf as usize);
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let output = received.clone();
+        Asyncc::spawn(
+            Box::new(async move {
+                for _ in 0..2 {
+                    let message = recv_message(7).await;
+                    output.lock().unwrap().push((message.sender, message.data().to_vec()));
+                }
+                0
+            }),
+            0,
+            TaskType::Other,
+        );
+        assert!(run() == (0, 1));
+        // The messages are posted by another process or the kernel.
+        msgbuf.queue.enqueue(Message::new(3, 7, b"ping")).unwrap();
+        msgbuf.queue.enqueue(Message::new(0, 7, &[0; 32])).unwrap();
+        assert!(run() == (0, 0));
+        assert!(Asyncc::dispatch_messages() == 2);
+        assert!(run() == (1, 0));
+        let received = received.lock().unwrap();
+        assert!(received[0] == (3, b"ping".to_vec()) && received[1] == (0, alloc::vec![0; 24]));
+    }
+
+    #[test]
+    fn resume_test() {
+        let executor: &'static Executor = Box::leak(Box::new(Executor::new()));
+        let msgbuf: &'static MsgBuf = Box::leak(Box::new(MsgBuf::new()));
+        Asyncc::reset(executor);
+        Asyncc::set_msgbuf(msgbuf as *const MsgBuf as usize);
+        let task_ref = Asyncc::spawn(Box::new(async { 0 }), 0, TaskType::Other).task_ref();
+        // The task is fetched but never executed, like the copy of it in a forked child.
+        assert!(executor.fetch() == Some(task_ref) && executor.fetch().is_none());
+        msgbuf.queue.enqueue(Message::resume(task_ref)).unwrap();
+        assert!(Asyncc::dispatch_messages() == 1);
+        assert!(run() == (1, 0));
+    }
+
+    #[test]
+    fn msgqueue_test() {
+        let msgbuf: &'static mut MsgBuf = Box::leak(Box::new(MsgBuf::new()));
+        for key in 0..MSGQUEUE_SIZE {
+            msgbuf.queue.enqueue(Message::new(0, key, &[])).unwrap();
+        }
+        assert!(msgbuf.queue.enqueue(Message::new(0, MSGQUEUE_SIZE, &[])).is_err());
+        assert!((0..MSGQUEUE_SIZE).all(|key| msgbuf.queue.dequeue().unwrap().key == key));
+        assert!(msgbuf.queue.dequeue().is_none());
+        // The user may scribble on the page, e.g. the first slot looks taken forever, and the
+        // kernel gives up instead of spinning.
+        let words = msgbuf as *mut MsgBuf as *mut usize;
+        unsafe {
+            core::ptr::write_bytes(words, 0, 2);
+            words.add(2).write(1);
+        }
+        assert!(msgbuf.queue.enqueue(Message::new(0, 0, &[])).is_err());
+    }
+}
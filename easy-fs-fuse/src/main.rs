@@ -1,9 +1,13 @@
 use clap::{App, Arg};
-use easy_fs::{BlockDevice, EasyFileSystem};
+use easy_fs::{BlockDevice, BlockFuture, DiskInodeType, EasyFileSystem, Inode};
 use std::fs::{read_dir, File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use time::driver::Driver;
+use time::{time_driver_impl, TICK_HZ};
 
 const BLOCK_SZ: usize = 512;
 
@@ -24,15 +28,56 @@ impl BlockDevice for BlockFile {
         assert_eq!(file.write(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
     }
 
+    fn read_block_async<'a>(&'a self, block_id: usize, buf: &'a mut [u8]) -> BlockFuture<'a> {
+        Box::pin(async move { self.read_block(block_id, buf) })
+    }
+
+    fn write_block_async<'a>(&'a self, block_id: usize, buf: &'a [u8]) -> BlockFuture<'a> {
+        Box::pin(async move { self.write_block(block_id, buf) })
+    }
+
     fn handle_irq(&self) {
         unimplemented!();
     }
 }
 
+/// Timestamps of inodes packed on the host are taken from the host clock.
+struct HostTimeDriver;
+
+impl Driver for HostTimeDriver {
+    fn now(&self) -> u64 {
+        let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        since_epoch.as_secs() * TICK_HZ + since_epoch.subsec_nanos() as u64 * TICK_HZ / 1_000_000_000
+    }
+
+    /// The packer never awaits timers.
+    fn set_alarm(&self, _timestamp: u64) {}
+}
+
+time_driver_impl!(static TIME_DRIVER: HostTimeDriver = HostTimeDriver);
+
 fn main() {
     easy_fs_pack().expect("Error when packing easy-fs!");
 }
 
+/// Copies the host directory tree into the easy-fs directory `dir`.
+fn pack_dir(host_dir: &Path, dir: &Inode) -> std::io::Result<()> {
+    for dir_entry in read_dir(host_dir)? {
+        let dir_entry = dir_entry?;
+        let name = dir_entry.file_name().into_string().unwrap();
+        if dir_entry.file_type()?.is_dir() {
+            let sub_dir = dir.mkdir(name.as_str()).unwrap();
+            pack_dir(&dir_entry.path(), &sub_dir)?;
+        } else {
+            let mut all_data: Vec<u8> = Vec::new();
+            File::open(dir_entry.path())?.read_to_end(&mut all_data)?;
+            let inode = dir.create(name.as_str()).unwrap();
+            inode.write_at(0, all_data.as_slice());
+        }
+    }
+    Ok(())
+}
+
 fn easy_fs_pack() -> std::io::Result<()> {
     let matches = App::new("EasyFileSystem packer")
         .arg(
@@ -56,6 +101,13 @@ fn easy_fs_pack() -> std::io::Result<()> {
                 .takes_value(true)
                 .help("shared object files dir(with backslash)"),
         )
+        .arg(
+            Arg::with_name("root")
+                .short("r")
+                .long("root")
+                .takes_value(true)
+                .help("dir whose tree is copied into the root of image"),
+        )
         .get_matches();
     let src_path = matches.value_of("source").unwrap();
     let target_path = matches.value_of("target").unwrap();
@@ -114,6 +166,10 @@ fn easy_fs_pack() -> std::io::Result<()> {
         // write data to easy-fs
         inode.write_at(0, all_data.as_slice());
     }
+    if let Some(root_path) = matches.value_of("root") {
+        pack_dir(Path::new(root_path), &root_inode)?;
+    }
+    efs.lock().sync();
     // list apps
     // for app in root_inode.ls() {
     //     println!("{}", app);
@@ -133,7 +189,7 @@ fn efs_test() -> std::io::Result<()> {
         f
     })));
     EasyFileSystem::create(block_file.clone(), 4096, 1);
-    let efs = EasyFileSystem::open(block_file.clone());
+    let efs = EasyFileSystem::open(block_file.clone(), 32);
     let root_inode = EasyFileSystem::root_inode(&efs);
     root_inode.create("filea");
     root_inode.create("fileb");
@@ -181,5 +237,99 @@ fn efs_test() -> std::io::Result<()> {
     random_str_test(1000 * BLOCK_SZ);
     random_str_test(2000 * BLOCK_SZ);
 
+    // hierarchical directories
+    assert!(root_inode.mkdir("dira").is_some());
+    assert!(root_inode.mkdir("dira").is_none());
+    assert!(root_inode.create("dira/filec").is_some());
+    assert!(root_inode.mkdir("dira/dirb").is_some());
+    assert!(root_inode.create("dirx/filed").is_none());
+    assert!(root_inode.create("filea/filed").is_none());
+    let filec = root_inode.find("/dira/./dirb/../filec").unwrap();
+    filec.write_at(0, greet_str.as_bytes());
+    let dira = root_inode.find("dira").unwrap();
+    assert!(dira.is_dir());
+    assert_eq!(dira.ls(), vec!["filec", "dirb"]);
+    assert_eq!(dira.find("filec").unwrap().size(), greet_str.len());
+    assert!(root_inode.find("dira/..").unwrap().find("filea").is_some());
+    assert!(!root_inode.rmdir("dira"));
+    assert!(!root_inode.unlink("dira/dirb"));
+    assert!(root_inode.unlink("dira/filec"));
+    assert!(root_inode.find("dira/filec").is_none());
+    assert!(root_inode.rmdir("dira/dirb"));
+    assert!(dira.ls().is_empty());
+    // freed dirent slots are reused
+    assert!(dira.create("filee").is_some());
+    assert_eq!(dira.size(), 4 * 32);
+    assert!(root_inode.unlink("dira/filee"));
+    assert!(root_inode.rmdir("dira"));
+    assert_eq!(root_inode.ls(), vec!["filea", "fileb"]);
+
+    // metadata and hard links
+    let root_stat = root_inode.stat();
+    assert_eq!((root_stat.ino, root_stat.nlink), (0, 2));
+    assert!(root_inode.mkdir("dirc").is_some());
+    assert_eq!(root_inode.stat().nlink, 3);
+    let fileb = root_inode.find("fileb").unwrap();
+    fileb.write_at(0, greet_str.as_bytes());
+    let stat = fileb.stat();
+    assert_eq!((stat.type_, stat.mode, stat.nlink), (DiskInodeType::File, 0o644, 1));
+    assert_eq!(stat.size as usize, greet_str.len());
+    assert!(stat.mtime >= root_stat.ctime);
+    assert!(root_inode.link("fileb", "dirc/filef").is_some());
+    assert!(root_inode.link("fileb", "dirc/filef").is_none());
+    assert!(root_inode.link("dirc", "dirg").is_none());
+    let filef = root_inode.find("dirc/filef").unwrap();
+    assert_eq!(filef.stat().ino, stat.ino);
+    assert_eq!(filef.stat().nlink, 2);
+    assert!(root_inode.unlink("fileb"));
+    assert_eq!(filef.stat().nlink, 1);
+    let len = filef.read_at(0, &mut buffer);
+    assert_eq!(greet_str, core::str::from_utf8(&buffer[..len]).unwrap());
+    assert!(root_inode.unlink("dirc/filef"));
+    assert!(root_inode.rmdir("dirc"));
+    assert_eq!(root_inode.stat().nlink, 2);
+    // the unlinked file is freed when its last handle is dropped
+    assert_eq!(filef.stat().nlink, 0);
+    let len = filef.read_at(0, &mut buffer);
+    assert_eq!(greet_str, core::str::from_utf8(&buffer[..len]).unwrap());
+    drop(fileb);
+    drop(filef);
+    let fileh = root_inode.create("fileh").unwrap();
+    assert_eq!((fileh.stat().ino, fileh.size()), (stat.ino, 0));
+    assert!(root_inode.unlink("fileh"));
+
+    // dirty blocks are written back by sync, and the data survives a small cache
+    efs.lock().sync();
+    let efs = EasyFileSystem::open(block_file.clone(), 4);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    filea.clear();
+    let data: Vec<u8> = (0..64 * BLOCK_SZ).map(|i| (i % 251) as u8).collect();
+    filea.write_at(0, &data);
+    filea.fsync();
+    let mut read_data = vec![0u8; data.len()];
+    assert_eq!(root_inode.find("filea").unwrap().read_at(0, &mut read_data), data.len());
+    assert_eq!(data, read_data);
+
+    // the async path shares the block cache with the sync one
+    fn block_on<F: std::future::Future>(fut: F) -> F::Output {
+        let mut fut = std::pin::pin!(fut);
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        loop {
+            if let std::task::Poll::Ready(ret) = fut.as_mut().poll(&mut cx) {
+                break ret;
+            }
+        }
+    }
+    let fileh = root_inode.create("fileh").unwrap();
+    assert_eq!(block_on(fileh.write_at_async(100, &data)), data.len());
+    assert_eq!(fileh.size(), 100 + data.len());
+    read_data.fill(0);
+    assert_eq!(fileh.read_at(100, &mut read_data), data.len());
+    assert_eq!(data, read_data);
+    read_data.fill(0);
+    assert_eq!(block_on(fileh.read_at_async(100, &mut read_data)), data.len());
+    assert_eq!(data, read_data);
+    assert_eq!(block_on(fileh.read_at_async(fileh.size(), &mut read_data)), 0);
+
     Ok(())
 }
@@ -7,36 +7,33 @@ extern crate alloc;
 use alloc::boxed::Box;
 use asyncc::*;
 
+/// The messages posted to this process, e.g. the resume messages of the kernel.
+static MSGBUF: MsgBuf = MsgBuf::new();
+
 /// This function need to be defined in kernel or user process. 
 #[link_section = ".text.entry"]
 #[no_mangle]
 pub fn _start(task_ref: Option<TaskRef>) -> Option<TaskRef> {
     if let Some(task_ref) = task_ref {
-        unsafe {
-            asyncc::Asyncc::set_curr(Some(task_ref));
-            let waker = asyncc::from_task(task_ref);
-            let mut cx = Context::from_waker(&waker);
-            let task = Task::from_ref(task_ref);
-            task.state.store(TaskState::Running as _, Ordering::Relaxed);
-            let fut = &mut *task.fut.as_ptr();
-            let mut future = Pin::new_unchecked(fut.as_mut());
-            match future.as_mut().poll(&mut cx) {
-                Poll::Ready(_) => { 
-                    Asyncc::set_cause(crate::Cause::Finish);
-                    None },
-                Poll::Pending => { 
-                    Asyncc::set_cause(crate::Cause::Await);
-                    task.state.store(TaskState::Pending as _, Ordering::Relaxed);
-                    Some(task.as_ref()) 
-                },
-            }
-        }
+        asyncc::Asyncc::set_curr(Some(task_ref));
+        asyncc::execute(task_ref)
     } else {
         let executor = asyncc::Asyncc::get_executor();
         if executor.state.load(Ordering::Relaxed) == ExecutorState::Ready as _ {
+            // The kernel only maps the heap area, the allocator is initialized here.
+            unsafe {
+                let allocator = &*(USER_HEAP_PTR as *const usize as *const LockedHeap<32>);
+                allocator.lock().init(USER_HEAP_PTR - USER_HEAP_SIZE, USER_HEAP_SIZE);
+            }
+            let msgbuf = &MSGBUF as *const MsgBuf as usize;
+            if syscall::sys_register_msg_buf(msgbuf) == 0 {
+                Asyncc::set_msgbuf(msgbuf);
+            }
             Asyncc::spawn(Box::new(main(0)), 0, asyncc::TaskType::Other);
             executor.state.store(ExecutorState::Running as _, Ordering::Relaxed);
         }
+        // The coroutines woken by the messages are fetched next time.
+        Asyncc::dispatch_messages();
         None
     }
 }
@@ -65,7 +62,7 @@ use core::future::Future;
 use core::pin::Pin;
 use core::ptr::NonNull;
 use core::sync::atomic::Ordering;
-use core::task::{Context, Poll};
+use core::task::Context;
 impl Future for Help {
     type Output = i32;
     fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> core::task::Poll<Self::Output> {
@@ -94,7 +91,7 @@ struct Global;
 
 #[global_allocator]
 static GLOBAL: Global = Global;
-const USER_HEAP_PTR: usize = 0xFFFFE000;
+use config::{USER_HEAP_PTR, USER_HEAP_SIZE};
 
 unsafe impl GlobalAlloc for Global {
     unsafe fn alloc(&self, layout: core::alloc::Layout) -> *mut u8 {
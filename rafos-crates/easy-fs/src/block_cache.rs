@@ -1,8 +1,8 @@
 use super::{BlockDevice, BLOCK_SZ};
-use alloc::collections::VecDeque;
 use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
+use hashbrown::HashMap;
 use lazy_static::*;
 use spin::Mutex;
 
@@ -27,6 +27,17 @@ impl BlockCache {
         }
     }
 
+    /// Creates a BlockCache of the data which has been read from disk.
+    pub fn from_data(block_id: usize, block_device: Arc<dyn BlockDevice>, cache: Vec<u8>) -> Self {
+        assert_eq!(cache.len(), BLOCK_SZ);
+        Self {
+            cache,
+            block_id,
+            block_device,
+            modified: false,
+        }
+    }
+
     fn addr_of_offset(&self, offset: usize) -> usize {
         &self.cache[offset] as *const _ as usize
     }
@@ -74,55 +85,172 @@ impl Drop for BlockCache {
     }
 }
 
-const BLOCK_CACHE_SIZE: usize = 16;
+/// Capacity of the cache before [`EasyFileSystem::open`](crate::EasyFileSystem::open) sets it.
+const DEFAULT_BLOCK_CACHE_CAPACITY: usize = 16;
+/// End of the LRU list.
+const NIL: usize = usize::MAX;
+
+struct LruNode {
+    block_id: usize,
+    cache: Arc<Mutex<BlockCache>>,
+    /// The more recently used one.
+    prev: usize,
+    /// The less recently used one.
+    next: usize,
+}
 
+/// A write-back LRU cache of blocks.
+///
+/// The nodes of LRU list are kept in `slots` and linked by index, and `map` finds the slot
+/// of a block. A dirty block is written back when it is evicted or synced.
 pub struct BlockCacheManager {
-    queue: VecDeque<(usize, Arc<Mutex<BlockCache>>)>,
+    capacity: usize,
+    map: HashMap<usize, usize>,
+    slots: Vec<Option<LruNode>>,
+    free_slots: Vec<usize>,
+    /// The most recently used node.
+    head: usize,
+    /// The least recently used node.
+    tail: usize,
 }
 
 impl BlockCacheManager {
-    pub fn new() -> Self {
+    pub fn new(capacity: usize) -> Self {
         Self {
-            queue: VecDeque::new(),
+            capacity,
+            map: HashMap::new(),
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            head: NIL,
+            tail: NIL,
+        }
+    }
+
+    fn node(&self, slot: usize) -> &LruNode {
+        self.slots[slot].as_ref().unwrap()
+    }
+
+    fn node_mut(&mut self, slot: usize) -> &mut LruNode {
+        self.slots[slot].as_mut().unwrap()
+    }
+
+    fn detach(&mut self, slot: usize) {
+        let (prev, next) = {
+            let node = self.node(slot);
+            (node.prev, node.next)
+        };
+        match prev {
+            NIL => self.head = next,
+            prev => self.node_mut(prev).next = next,
         }
+        match next {
+            NIL => self.tail = prev,
+            next => self.node_mut(next).prev = prev,
+        }
+    }
+
+    fn attach_front(&mut self, slot: usize) {
+        let head = self.head;
+        {
+            let node = self.node_mut(slot);
+            node.prev = NIL;
+            node.next = head;
+        }
+        match head {
+            NIL => self.tail = slot,
+            head => self.node_mut(head).prev = slot,
+        }
+        self.head = slot;
     }
 
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        assert!(capacity > 0);
+        self.capacity = capacity;
+        self.shrink(capacity);
+    }
+
+    /// Evicts the least recently used blocks which are not in use, until at most `len`
+    /// blocks are cached.
+    fn shrink(&mut self, len: usize) {
+        let mut slot = self.tail;
+        while self.len() > len && slot != NIL {
+            let prev = self.node(slot).prev;
+            if Arc::strong_count(&self.node(slot).cache) == 1 {
+                self.detach(slot);
+                let node = self.slots[slot].take().unwrap();
+                self.map.remove(&node.block_id);
+                self.free_slots.push(slot);
+                // write back by `BlockCache::drop`
+                drop(node);
+            }
+            slot = prev;
+        }
+    }
+
+    /// If all cached blocks are in use, the cache grows beyond its capacity instead of
+    /// evicting, and shrinks back when the blocks are released.
     pub fn get_block_cache(
         &mut self,
         block_id: usize,
         block_device: Arc<dyn BlockDevice>,
     ) -> Arc<Mutex<BlockCache>> {
-        if let Some(pair) = self.queue.iter().find(|pair| pair.0 == block_id) {
-            Arc::clone(&pair.1)
-        } else {
-            // substitute
-            if self.queue.len() == BLOCK_CACHE_SIZE {
-                // from front to tail
-                if let Some((idx, _)) = self
-                    .queue
-                    .iter()
-                    .enumerate()
-                    .find(|(_, pair)| Arc::strong_count(&pair.1) == 1)
-                {
-                    self.queue.drain(idx..=idx);
-                } else {
-                    panic!("Run out of BlockCache!");
-                }
-            }
-            // load block into mem and push back
-            let block_cache = Arc::new(Mutex::new(BlockCache::new(
-                block_id,
-                Arc::clone(&block_device),
-            )));
-            self.queue.push_back((block_id, Arc::clone(&block_cache)));
-            block_cache
+        if let Some(&slot) = self.map.get(&block_id) {
+            self.detach(slot);
+            self.attach_front(slot);
+            return Arc::clone(&self.node(slot).cache);
         }
+        // load block into mem and push front
+        self.insert(BlockCache::new(block_id, block_device))
+    }
+
+    pub fn contains(&self, block_id: usize) -> bool {
+        self.map.contains_key(&block_id)
+    }
+
+    /// Inserts a block which is not cached yet as the most recently used one.
+    pub fn insert(&mut self, block_cache: BlockCache) -> Arc<Mutex<BlockCache>> {
+        let block_id = block_cache.block_id;
+        assert!(!self.contains(block_id));
+        self.shrink(self.capacity - 1);
+        let block_cache = Arc::new(Mutex::new(block_cache));
+        let node = LruNode {
+            block_id,
+            cache: Arc::clone(&block_cache),
+            prev: NIL,
+            next: NIL,
+        };
+        let slot = match self.free_slots.pop() {
+            Some(slot) => {
+                self.slots[slot] = Some(node);
+                slot
+            }
+            None => {
+                self.slots.push(Some(node));
+                self.slots.len() - 1
+            }
+        };
+        self.map.insert(block_id, slot);
+        self.attach_front(slot);
+        block_cache
+    }
+
+    /// Returns all cached blocks, so that they can be synced without the manager locked.
+    fn caches(&self) -> Vec<Arc<Mutex<BlockCache>>> {
+        self.slots
+            .iter()
+            .flatten()
+            .map(|node| Arc::clone(&node.cache))
+            .collect()
     }
 }
 
 lazy_static! {
     pub static ref BLOCK_CACHE_MANAGER: Mutex<BlockCacheManager> =
-        Mutex::new(BlockCacheManager::new());
+        Mutex::new(BlockCacheManager::new(DEFAULT_BLOCK_CACHE_CAPACITY));
 }
 
 pub fn get_block_cache(
@@ -134,9 +262,28 @@ pub fn get_block_cache(
         .get_block_cache(block_id, block_device)
 }
 
+/// Reads the block into cache asynchronously if it is not cached.
+pub async fn load_block_cache(block_id: usize, block_device: Arc<dyn BlockDevice>) {
+    if BLOCK_CACHE_MANAGER.lock().contains(block_id) {
+        return;
+    }
+    let mut cache = vec![0u8; BLOCK_SZ];
+    block_device.read_block_async(block_id, &mut cache).await;
+    let mut manager = BLOCK_CACHE_MANAGER.lock();
+    // the block may be loaded and modified by others while waiting
+    if !manager.contains(block_id) {
+        manager.insert(BlockCache::from_data(block_id, block_device, cache));
+    }
+}
+
+pub fn set_block_cache_capacity(capacity: usize) {
+    BLOCK_CACHE_MANAGER.lock().set_capacity(capacity);
+}
+
+/// Writes back all dirty blocks.
 pub fn block_cache_sync_all() {
-    let manager = BLOCK_CACHE_MANAGER.lock();
-    for (_, cache) in manager.queue.iter() {
+    let caches = BLOCK_CACHE_MANAGER.lock().caches();
+    for cache in caches {
         cache.lock().sync();
     }
 }
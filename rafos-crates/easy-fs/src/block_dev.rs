@@ -1,7 +1,21 @@
+use alloc::boxed::Box;
 use core::any::Any;
+use core::future::Future;
+use core::pin::Pin;
+
+/// The future of an asynchronous block request.
+pub type BlockFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + Sync + 'a>>;
 
 pub trait BlockDevice: Send + Sync + Any {
     fn read_block(&self, block_id: usize, buf: &mut [u8]);
     fn write_block(&self, block_id: usize, buf: &[u8]);
+    /// Reads a block without blocking, the future is woken when the request completes.
+    ///
+    /// The future must be polled to completion, since the device may access `buf` until then.
+    fn read_block_async<'a>(&'a self, block_id: usize, buf: &'a mut [u8]) -> BlockFuture<'a>;
+    /// Writes a block without blocking, the future is woken when the request completes.
+    ///
+    /// The future must be polled to completion, since the device may access `buf` until then.
+    fn write_block_async<'a>(&'a self, block_id: usize, buf: &'a [u8]) -> BlockFuture<'a>;
     fn handle_irq(&self);
 }
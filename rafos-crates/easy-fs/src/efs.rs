@@ -1,9 +1,9 @@
 use super::{
-    block_cache_sync_all, get_block_cache, Bitmap, BlockDevice, DiskInode, DiskInodeType, Inode,
+    block_cache_sync_all, get_block_cache, set_block_cache_capacity, Bitmap, BlockDevice, DiskInode, DiskInodeType, Inode,
     SuperBlock,
 };
 use crate::BLOCK_SZ;
-use alloc::sync::Arc;
+use alloc::{collections::{BTreeMap, BTreeSet}, sync::Arc};
 use spin::Mutex;
 
 pub struct EasyFileSystem {
@@ -12,6 +12,10 @@ pub struct EasyFileSystem {
     pub data_bitmap: Bitmap,
     inode_area_start_block: u32,
     data_area_start_block: u32,
+    /// The number of [`Inode`] handles of each inode.
+    handles: BTreeMap<u32, usize>,
+    /// The inodes which have been unlinked, and are freed when their last handle is dropped.
+    orphans: BTreeSet<u32>,
 }
 
 type DataBlock = [u8; BLOCK_SZ];
@@ -41,6 +45,8 @@ impl EasyFileSystem {
             data_bitmap,
             inode_area_start_block: 1 + inode_bitmap_blocks,
             data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,
+            handles: BTreeMap::new(),
+            orphans: BTreeSet::new(),
         };
         // clear all blocks
         for i in 0..total_blocks {
@@ -74,15 +80,27 @@ impl EasyFileSystem {
             .modify(root_inode_offset, |disk_inode: &mut DiskInode| {
                 disk_inode.initialize(DiskInodeType::Directory);
             });
+        let efs = Arc::new(Mutex::new(efs));
+        // the parent of root is itself
+        let root_inode = Self::root_inode(&efs);
+        root_inode.init_dir_entries(0, 0, &mut efs.lock());
         block_cache_sync_all();
-        Arc::new(Mutex::new(efs))
+        efs
     }
 
-    pub fn open(block_device: Arc<dyn BlockDevice>) -> Arc<Mutex<Self>> {
+    /// Opens the file system with at most `cache_capacity` blocks cached in memory.
+    ///
+    /// The block cache is shared by all opened file systems.
+    pub fn open(block_device: Arc<dyn BlockDevice>, cache_capacity: usize) -> Arc<Mutex<Self>> {
+        set_block_cache_capacity(cache_capacity);
         // read SuperBlock
         get_block_cache(0, Arc::clone(&block_device))
             .lock()
             .read(0, |super_block: &SuperBlock| {
+                assert!(
+                    !super_block.is_outdated(),
+                    "Outdated EFS image without inode metadata, please repack it!"
+                );
                 assert!(super_block.is_valid(), "Error loading EFS!");
                 let inode_total_blocks =
                     super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
@@ -95,17 +113,43 @@ impl EasyFileSystem {
                     ),
                     inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
                     data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
+                    handles: BTreeMap::new(),
+                    orphans: BTreeSet::new(),
                 };
                 Arc::new(Mutex::new(efs))
             })
     }
 
+    /// Writes back all dirty blocks.
+    pub fn sync(&self) {
+        block_cache_sync_all();
+    }
+
     pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Inode {
-        let block_device = Arc::clone(&efs.lock().block_device);
         // acquire efs lock temporarily
-        let (block_id, block_offset) = efs.lock().get_disk_inode_pos(0);
-        // release efs lock
-        Inode::new(block_id, block_offset, Arc::clone(efs), block_device)
+        Inode::new(0, efs, &mut efs.lock())
+    }
+
+    /// Counts a new handle of the inode.
+    pub(crate) fn open_inode(&mut self, inode_id: u32) {
+        *self.handles.entry(inode_id).or_insert(0) += 1;
+    }
+
+    /// Drops a handle of the inode, returns true if it is the last handle of an unlinked
+    /// inode, which must be freed now.
+    pub(crate) fn close_inode(&mut self, inode_id: u32) -> bool {
+        let handles = self.handles.get_mut(&inode_id).unwrap();
+        *handles -= 1;
+        if *handles > 0 {
+            return false;
+        }
+        self.handles.remove(&inode_id);
+        self.orphans.remove(&inode_id)
+    }
+
+    /// Marks the inode as unlinked, it is freed when its last handle is dropped.
+    pub(crate) fn orphan_inode(&mut self, inode_id: u32) {
+        self.orphans.insert(inode_id);
     }
 
     pub fn get_disk_inode_pos(&self, inode_id: u32) -> (u32, usize) {
@@ -118,6 +162,14 @@ impl EasyFileSystem {
         )
     }
 
+    /// The inverse of [`Self::get_disk_inode_pos`].
+    pub fn get_inode_id(&self, block_id: u32, block_offset: usize) -> u32 {
+        let inode_size = core::mem::size_of::<DiskInode>();
+        let inodes_per_block = (BLOCK_SZ / inode_size) as u32;
+        (block_id - self.inode_area_start_block) * inodes_per_block
+            + (block_offset / inode_size) as u32
+    }
+
     pub fn get_data_block_id(&self, data_block_id: u32) -> u32 {
         self.data_area_start_block + data_block_id
     }
@@ -126,6 +178,16 @@ impl EasyFileSystem {
         self.inode_bitmap.alloc(&self.block_device).unwrap() as u32
     }
 
+    pub fn dealloc_inode(&mut self, inode_id: u32) {
+        let (block_id, block_offset) = self.get_disk_inode_pos(inode_id);
+        get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(block_offset, |disk_inode: &mut DiskInode| {
+                disk_inode.initialize(DiskInodeType::File);
+            });
+        self.inode_bitmap.dealloc(&self.block_device, inode_id as usize)
+    }
+
     /// Return a block ID not ID in the data area.
     pub fn alloc_data(&mut self) -> u32 {
         self.data_bitmap.alloc(&self.block_device).unwrap() as u32 + self.data_area_start_block
@@ -2,10 +2,14 @@ use super::{get_block_cache, BlockDevice, BLOCK_SZ};
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::fmt::{Debug, Formatter, Result};
+use time::Instant;
 
-const EFS_MAGIC: u32 = 0x3b800001;
-const INODE_DIRECT_COUNT: usize = 28;
-const NAME_LENGTH_LIMIT: usize = 27;
+/// The low byte of magic is the version of on-disk format.
+const EFS_MAGIC: u32 = 0x3b800002;
+/// Images before the inode metadata was added.
+const EFS_MAGIC_V1: u32 = 0x3b800001;
+const INODE_DIRECT_COUNT: usize = 18;
+pub const NAME_LENGTH_LIMIT: usize = 27;
 const INODE_INDIRECT1_COUNT: usize = BLOCK_SZ / 4;
 const INODE_INDIRECT2_COUNT: usize = INODE_INDIRECT1_COUNT * INODE_INDIRECT1_COUNT;
 const DIRECT_BOUND: usize = INODE_DIRECT_COUNT;
@@ -56,9 +60,12 @@ impl SuperBlock {
     pub fn is_valid(&self) -> bool {
         self.magic == EFS_MAGIC
     }
+    pub fn is_outdated(&self) -> bool {
+        self.magic == EFS_MAGIC_V1
+    }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum DiskInodeType {
     File,
     Directory,
@@ -67,24 +74,59 @@ pub enum DiskInodeType {
 type IndirectBlock = [u32; BLOCK_SZ / 4];
 type DataBlock = [u8; BLOCK_SZ];
 
+/// Default permission bits of a new regular file.
+pub const FILE_MODE: u16 = 0o644;
+/// Default permission bits of a new directory.
+pub const DIR_MODE: u16 = 0o755;
+
+/// Returns the timestamp stored in inodes, in seconds.
+pub fn timestamp() -> u64 {
+    Instant::now().as_secs()
+}
+
+/// The size is kept as 128 bytes, so 4 inodes fit in a block.
 #[repr(C)]
 pub struct DiskInode {
     pub size: u32,
     pub direct: [u32; INODE_DIRECT_COUNT],
     pub indirect1: u32,
     pub indirect2: u32,
+    /// Permission bits.
+    pub mode: u16,
+    /// Number of directory entries referring to this inode.
+    pub nlink: u16,
+    pub uid: u32,
+    pub gid: u32,
+    pub atime: u64,
+    pub mtime: u64,
+    pub ctime: u64,
     type_: DiskInodeType,
 }
 
 impl DiskInode {
     /// indirect1 and indirect2 block are allocated only when they are needed.
+    ///
+    /// A directory starts with 2 links, one from its parent and one from its `.` entry.
     pub fn initialize(&mut self, type_: DiskInodeType) {
         self.size = 0;
         self.direct.iter_mut().for_each(|v| *v = 0);
         self.indirect1 = 0;
         self.indirect2 = 0;
+        (self.mode, self.nlink) = match type_ {
+            DiskInodeType::File => (FILE_MODE, 1),
+            DiskInodeType::Directory => (DIR_MODE, 2),
+        };
+        self.uid = 0;
+        self.gid = 0;
+        let now = timestamp();
+        self.atime = now;
+        self.mtime = now;
+        self.ctime = now;
         self.type_ = type_;
     }
+    pub fn type_(&self) -> DiskInodeType {
+        self.type_
+    }
     pub fn is_dir(&self) -> bool {
         self.type_ == DiskInodeType::Directory
     }
@@ -406,4 +448,8 @@ impl DirEntry {
     pub fn inode_number(&self) -> u32 {
         self.inode_number
     }
+    /// An entry with empty name is a free slot of the directory.
+    pub fn is_empty(&self) -> bool {
+        self.name[0] == 0
+    }
 }
@@ -11,8 +11,11 @@ mod vfs;
 
 pub const BLOCK_SZ: usize = 512;
 use bitmap::Bitmap;
-use block_cache::{block_cache_sync_all, get_block_cache};
-pub use block_dev::BlockDevice;
+use block_cache::{
+    block_cache_sync_all, get_block_cache, load_block_cache, set_block_cache_capacity,
+};
+pub use block_dev::{BlockDevice, BlockFuture};
 pub use efs::EasyFileSystem;
 use layout::*;
-pub use vfs::Inode;
+pub use layout::DiskInodeType;
+pub use vfs::{Inode, InodeStat};
@@ -1,12 +1,38 @@
 use super::{
-    block_cache_sync_all, get_block_cache, BlockDevice, DirEntry, DiskInode, DiskInodeType,
-    EasyFileSystem, DIRENT_SZ,
+    get_block_cache, load_block_cache, BlockDevice, DirEntry, DiskInode, DiskInodeType,
+    timestamp, EasyFileSystem, BLOCK_SZ, DIRENT_SZ, NAME_LENGTH_LIMIT,
 };
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use spin::{Mutex, MutexGuard};
 
+/// Splits a path into its parent directory and the last component.
+fn split_path(path: &str) -> (&str, &str) {
+    let path = path.trim_end_matches('/');
+    match path.rfind('/') {
+        Some(pos) => (&path[..pos], &path[pos + 1..]),
+        None => ("", path),
+    }
+}
+
+/// Metadata of an inode, returned by [`Inode::stat`].
+#[derive(Debug, Clone, Copy)]
+pub struct InodeStat {
+    pub ino: u32,
+    pub type_: DiskInodeType,
+    pub mode: u16,
+    pub nlink: u16,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u32,
+    /// Number of blocks used by data and index blocks.
+    pub blocks: u32,
+    pub atime: u64,
+    pub mtime: u64,
+    pub ctime: u64,
+}
+
 pub struct Inode {
     block_id: usize,
     block_offset: usize,
@@ -15,18 +41,19 @@ pub struct Inode {
 }
 
 impl Inode {
-    /// We should not acquire efs lock here.
-    pub fn new(
-        block_id: u32,
-        block_offset: usize,
-        fs: Arc<Mutex<EasyFileSystem>>,
-        block_device: Arc<dyn BlockDevice>,
+    /// Opens a handle of the inode `inode_id`, with the efs lock held.
+    pub(crate) fn new(
+        inode_id: u32,
+        efs: &Arc<Mutex<EasyFileSystem>>,
+        fs: &mut EasyFileSystem,
     ) -> Self {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        fs.open_inode(inode_id);
         Self {
             block_id: block_id as usize,
             block_offset,
-            fs,
-            block_device,
+            fs: efs.clone(),
+            block_device: fs.block_device.clone(),
         }
     }
 
@@ -42,9 +69,28 @@ impl Inode {
             .modify(self.block_offset, f)
     }
 
+    /// Returns the inode number in this file system.
+    fn inode_id(&self, fs: &EasyFileSystem) -> u32 {
+        fs.get_inode_id(self.block_id as u32, self.block_offset)
+    }
+
+    /// Returns a new handle of the same inode.
+    fn duplicate(&self) -> Self {
+        let mut fs = self.fs.lock();
+        let inode_id = self.inode_id(&fs);
+        fs.open_inode(inode_id);
+        Self {
+            block_id: self.block_id,
+            block_offset: self.block_offset,
+            fs: self.fs.clone(),
+            block_device: self.block_device.clone(),
+        }
+    }
+
     fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
-        // assert it is a directory
-        assert!(disk_inode.is_dir());
+        if !disk_inode.is_dir() {
+            return None;
+        }
         let file_count = (disk_inode.size as usize) / DIRENT_SZ;
         let mut dirent = DirEntry::empty();
         for i in 0..file_count {
@@ -52,28 +98,33 @@ impl Inode {
                 disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device,),
                 DIRENT_SZ,
             );
-            if dirent.name() == name {
+            if !dirent.is_empty() && dirent.name() == name {
                 return Some(dirent.inode_number() as u32);
             }
         }
         None
     }
 
-    pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
-        let fs = self.fs.lock();
+    /// Finds an entry of this directory by name.
+    fn find_child(&self, name: &str) -> Option<Arc<Inode>> {
+        let mut fs = self.fs.lock();
         self.read_disk_inode(|disk_inode| {
-            self.find_inode_id(name, disk_inode).map(|inode_id| {
-                let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
-                Arc::new(Self::new(
-                    block_id,
-                    block_offset,
-                    self.fs.clone(),
-                    self.block_device.clone(),
-                ))
-            })
+            self.find_inode_id(name, disk_inode)
+                .map(|inode_id| Arc::new(Self::new(inode_id, &self.fs, &mut fs)))
         })
     }
 
+    /// Finds an inode by path such as `a/b/c`, which is resolved from this inode.
+    ///
+    /// The leading `/` is ignored, so an absolute path must be resolved from the root inode.
+    pub fn find(&self, path: &str) -> Option<Arc<Inode>> {
+        let mut inode = Arc::new(self.duplicate());
+        for name in path.split('/').filter(|name| !name.is_empty() && *name != ".") {
+            inode = inode.find_child(name)?;
+        }
+        Some(inode)
+    }
+
     fn increase_size(
         &self,
         new_size: u32,
@@ -91,15 +142,77 @@ impl Inode {
         disk_inode.increase_size(new_size, v, &self.block_device);
     }
 
-    pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+    /// Writes `.` and `..` entries into an empty directory.
+    pub(crate) fn init_dir_entries(
+        &self,
+        inode_id: u32,
+        parent_id: u32,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) {
+        self.modify_disk_inode(|disk_inode| {
+            assert!(disk_inode.is_dir() && disk_inode.size == 0);
+            self.increase_size(2 * DIRENT_SZ as u32, disk_inode, fs);
+            disk_inode.write_at(0, DirEntry::new(".", inode_id).as_bytes(), &self.block_device);
+            disk_inode.write_at(
+                DIRENT_SZ,
+                DirEntry::new("..", parent_id).as_bytes(),
+                &self.block_device,
+            );
+        });
+    }
+
+    /// Writes a new entry into a free slot of the directory, or appends it.
+    fn insert_dirent(
+        &self,
+        name: &str,
+        inode_id: u32,
+        disk_inode: &mut DiskInode,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) {
+        let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+        let mut dirent = DirEntry::empty();
+        let free_slot = (0..file_count).find(|i| {
+            disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device);
+            dirent.is_empty()
+        });
+        let slot = match free_slot {
+            Some(slot) => slot,
+            None => {
+                self.increase_size(((file_count + 1) * DIRENT_SZ) as u32, disk_inode, fs);
+                file_count
+            }
+        };
+        disk_inode.write_at(
+            slot * DIRENT_SZ,
+            DirEntry::new(name, inode_id).as_bytes(),
+            &self.block_device,
+        );
+    }
+
+    /// Clears the entry of the directory by name, returning its inode number.
+    ///
+    /// The slot will be reused by [`Self::insert_dirent`], so the size of directory never shrinks.
+    fn remove_dirent(&self, name: &str, disk_inode: &mut DiskInode) -> Option<u32> {
+        let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+        let mut dirent = DirEntry::empty();
+        for i in 0..file_count {
+            disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device);
+            if !dirent.is_empty() && dirent.name() == name {
+                disk_inode.write_at(i * DIRENT_SZ, DirEntry::empty().as_bytes(), &self.block_device);
+                return Some(dirent.inode_number());
+            }
+        }
+        None
+    }
+
+    /// Creates an entry of this directory.
+    fn create_child(&self, name: &str, type_: DiskInodeType) -> Option<Arc<Inode>> {
         let mut fs = self.fs.lock();
-        let op = |root_inode: &mut DiskInode| {
-            // assert it is a directory
-            assert!(root_inode.is_dir());
+        let op = |dir_inode: &DiskInode| {
             // has the file been created?
-            self.find_inode_id(name, root_inode)
+            !dir_inode.is_dir() || self.find_inode_id(name, dir_inode).is_some()
         };
-        if self.modify_disk_inode(op).is_some() {
+        if self.read_disk_inode(op) {
             return None;
         }
         // create a new file
@@ -110,35 +223,140 @@ impl Inode {
         get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
             .lock()
             .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
-                new_inode.initialize(DiskInodeType::File);
+                new_inode.initialize(type_);
             });
-        self.modify_disk_inode(|root_inode| {
-            // append file in the dirent
-            let file_count = (root_inode.size as usize) / DIRENT_SZ;
-            let new_size = (file_count + 1) * DIRENT_SZ;
-            // increase size
-            self.increase_size(new_size as u32, root_inode, &mut fs);
-            // write dirent
-            let dirent = DirEntry::new(name, new_inode_id);
-            root_inode.write_at(
-                file_count * DIRENT_SZ,
-                dirent.as_bytes(),
-                &self.block_device,
-            );
+        let new_inode = Self::new(new_inode_id, &self.fs, &mut fs);
+        if type_ == DiskInodeType::Directory {
+            let parent_id = self.inode_id(&fs);
+            new_inode.init_dir_entries(new_inode_id, parent_id, &mut fs);
+        }
+        // append file in the dirent
+        self.modify_disk_inode(|dir_inode| {
+            self.insert_dirent(name, new_inode_id, dir_inode, &mut fs);
+            // `..` of the new directory
+            if type_ == DiskInodeType::Directory {
+                dir_inode.nlink += 1;
+            }
+            dir_inode.mtime = timestamp();
+            dir_inode.ctime = dir_inode.mtime;
         });
-
-        let (block_id, block_offset) = fs.get_disk_inode_pos(new_inode_id);
-        block_cache_sync_all();
         // return inode
-        Some(Arc::new(Self::new(
-            block_id,
-            block_offset,
-            self.fs.clone(),
-            self.block_device.clone(),
-        )))
+        Some(Arc::new(new_inode))
         // release efs lock automatically by compiler
     }
 
+    /// Creates an inode by path, whose parent directory must exist.
+    fn create_inode(&self, path: &str, type_: DiskInodeType) -> Option<Arc<Inode>> {
+        let (parent, name) = split_path(path);
+        if name.is_empty() || name == "." || name == ".." || name.len() > NAME_LENGTH_LIMIT {
+            return None;
+        }
+        self.find(parent)?.create_child(name, type_)
+    }
+
+    /// Creates a regular file by path.
+    pub fn create(&self, path: &str) -> Option<Arc<Inode>> {
+        self.create_inode(path, DiskInodeType::File)
+    }
+
+    /// Creates a directory by path.
+    pub fn mkdir(&self, path: &str) -> Option<Arc<Inode>> {
+        self.create_inode(path, DiskInodeType::Directory)
+    }
+
+    /// Removes the entry `name` of this directory.
+    ///
+    /// When the last link is removed, the inode with its data blocks is freed by its last
+    /// handle, so the handles in use can still read and write it.
+    fn remove_child(&self, name: &str, inode: &Inode) -> bool {
+        let mut fs = self.fs.lock();
+        let is_dir = inode.read_disk_inode(|disk_inode| disk_inode.is_dir());
+        let Some(inode_id) = self.modify_disk_inode(|dir_inode| {
+            let inode_id = self.remove_dirent(name, dir_inode)?;
+            // `..` of the removed directory
+            if is_dir {
+                dir_inode.nlink -= 1;
+            }
+            dir_inode.mtime = timestamp();
+            dir_inode.ctime = dir_inode.mtime;
+            Some(inode_id)
+        }) else {
+            return false;
+        };
+        let nlink = inode.modify_disk_inode(|disk_inode| {
+            disk_inode.nlink -= 1;
+            disk_inode.ctime = timestamp();
+            disk_inode.nlink
+        });
+        // an empty directory is only linked by its `.` entry now
+        if is_dir || nlink == 0 {
+            fs.orphan_inode(inode_id);
+        }
+        true
+    }
+
+    /// Creates a hard link `new_path` to the regular file `old_path`.
+    pub fn link(&self, old_path: &str, new_path: &str) -> Option<Arc<Inode>> {
+        let inode = self.find(old_path)?;
+        let (parent, name) = split_path(new_path);
+        if name.is_empty() || name == "." || name == ".." || name.len() > NAME_LENGTH_LIMIT {
+            return None;
+        }
+        let parent = self.find(parent)?;
+        let mut fs = self.fs.lock();
+        let inode_id = inode.inode_id(&fs);
+        if inode.read_disk_inode(|disk_inode| disk_inode.is_dir() || disk_inode.nlink == u16::MAX)
+            || parent.read_disk_inode(|dir_inode| {
+                !dir_inode.is_dir() || parent.find_inode_id(name, dir_inode).is_some()
+            })
+        {
+            return None;
+        }
+        parent.modify_disk_inode(|dir_inode| {
+            parent.insert_dirent(name, inode_id, dir_inode, &mut fs);
+            dir_inode.mtime = timestamp();
+            dir_inode.ctime = dir_inode.mtime;
+        });
+        inode.modify_disk_inode(|disk_inode| {
+            disk_inode.nlink += 1;
+            disk_inode.ctime = timestamp();
+        });
+        Some(inode)
+    }
+
+    /// Removes a regular file by path.
+    pub fn unlink(&self, path: &str) -> bool {
+        let (parent, name) = split_path(path);
+        if name.is_empty() || name == "." || name == ".." {
+            return false;
+        }
+        let Some(parent) = self.find(parent) else {
+            return false;
+        };
+        match parent.find_child(name) {
+            Some(inode) if !inode.is_dir() => parent.remove_child(name, &inode),
+            _ => false,
+        }
+    }
+
+    /// Removes an empty directory by path.
+    pub fn rmdir(&self, path: &str) -> bool {
+        let (parent, name) = split_path(path);
+        if name.is_empty() || name == "." || name == ".." {
+            return false;
+        }
+        let Some(parent) = self.find(parent) else {
+            return false;
+        };
+        match parent.find_child(name) {
+            Some(inode) if inode.is_dir() && inode.ls().is_empty() => {
+                parent.remove_child(name, &inode)
+            }
+            _ => false,
+        }
+    }
+
+    /// Lists the entries of this directory, except `.` and `..`.
     pub fn ls(&self) -> Vec<String> {
         let _fs = self.fs.lock();
         self.read_disk_inode(|disk_inode| {
@@ -150,29 +368,119 @@ impl Inode {
                     disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device,),
                     DIRENT_SZ,
                 );
+                if dirent.is_empty() || dirent.name() == "." || dirent.name() == ".." {
+                    continue;
+                }
                 v.push(String::from(dirent.name()));
             }
             v
         })
     }
 
+    /// Returns the size of the file in bytes.
+    pub fn size(&self) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
+
+    /// Returns the metadata of the inode.
+    pub fn stat(&self) -> InodeStat {
+        let fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| InodeStat {
+            ino: self.inode_id(&fs),
+            type_: disk_inode.type_(),
+            mode: disk_inode.mode,
+            nlink: disk_inode.nlink,
+            uid: disk_inode.uid,
+            gid: disk_inode.gid,
+            size: disk_inode.size,
+            blocks: DiskInode::total_blocks(disk_inode.size),
+            atime: disk_inode.atime,
+            mtime: disk_inode.mtime,
+            ctime: disk_inode.ctime,
+        })
+    }
+
+    /// Returns true if the inode is a directory.
+    pub fn is_dir(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+
+    /// The access time is only written back when it changes, since it is kept in seconds.
     pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
         let _fs = self.fs.lock();
-        self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
+        let now = timestamp();
+        let (size, atime) = self.read_disk_inode(|disk_inode| {
+            (disk_inode.read_at(offset, buf, &self.block_device), disk_inode.atime)
+        });
+        if atime != now {
+            self.modify_disk_inode(|disk_inode| disk_inode.atime = now);
+        }
+        size
     }
 
     pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
         let mut fs = self.fs.lock();
         let size = self.modify_disk_inode(|disk_inode| {
             self.increase_size((offset + buf.len()) as u32, disk_inode, &mut fs);
+            disk_inode.mtime = timestamp();
+            disk_inode.ctime = disk_inode.mtime;
             disk_inode.write_at(offset, buf, &self.block_device)
         });
-        block_cache_sync_all();
         size
     }
 
-    pub fn clear(&self) {
-        let mut fs = self.fs.lock();
+    /// Returns the block id of the `inner_id`th data block, which must have been allocated.
+    fn data_block_id(&self, inner_id: usize) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            disk_inode.get_block_id(inner_id as u32, &self.block_device) as usize
+        })
+    }
+
+    /// Like [`Self::read_at`], but the data blocks missing in the cache are read
+    /// asynchronously, so the coroutine yields while waiting for the device.
+    ///
+    /// The index blocks are still read synchronously.
+    pub async fn read_at_async(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let end = (offset + buf.len()).min(self.size());
+        let mut start = offset;
+        while start < end {
+            let end_current_block = ((start / BLOCK_SZ + 1) * BLOCK_SZ).min(end);
+            let block_id = self.data_block_id(start / BLOCK_SZ);
+            load_block_cache(block_id, Arc::clone(&self.block_device)).await;
+            self.read_at(start, &mut buf[start - offset..end_current_block - offset]);
+            start = end_current_block;
+        }
+        end.saturating_sub(offset)
+    }
+
+    /// Like [`Self::write_at`], but the data blocks missing in the cache are read
+    /// asynchronously before they are modified.
+    ///
+    /// The blocks are written back when they are evicted or synced.
+    pub async fn write_at_async(&self, offset: usize, buf: &[u8]) -> usize {
+        let end = offset + buf.len();
+        {
+            let mut fs = self.fs.lock();
+            self.modify_disk_inode(|disk_inode| {
+                self.increase_size(end as u32, disk_inode, &mut fs);
+            });
+        }
+        let mut start = offset;
+        while start < end {
+            let end_current_block = ((start / BLOCK_SZ + 1) * BLOCK_SZ).min(end);
+            let block_id = self.data_block_id(start / BLOCK_SZ);
+            load_block_cache(block_id, Arc::clone(&self.block_device)).await;
+            self.write_at(start, &buf[start - offset..end_current_block - offset]);
+            start = end_current_block;
+        }
+        buf.len()
+    }
+
+    /// Frees all data blocks of the inode with the efs lock held.
+    fn clear_data(&self, fs: &mut MutexGuard<EasyFileSystem>) {
         self.modify_disk_inode(|disk_inode| {
             let size = disk_inode.size;
             let data_blocks_dealloc = disk_inode.clear_size(&self.block_device);
@@ -180,7 +488,32 @@ impl Inode {
             for data_block in data_blocks_dealloc.into_iter() {
                 fs.dealloc_data(data_block);
             }
+            disk_inode.mtime = timestamp();
+            disk_inode.ctime = disk_inode.mtime;
         });
-        block_cache_sync_all();
+    }
+
+    pub fn clear(&self) {
+        let mut fs = self.fs.lock();
+        self.clear_data(&mut fs);
+    }
+
+    /// Writes back the dirty blocks of this file system.
+    ///
+    /// The block cache does not know which blocks belong to an inode, so all of them are synced.
+    pub fn fsync(&self) {
+        self.fs.lock().sync();
+    }
+}
+
+impl Drop for Inode {
+    /// Frees the unlinked inode if this is its last handle.
+    fn drop(&mut self) {
+        let mut fs = self.fs.lock();
+        let inode_id = self.inode_id(&fs);
+        if fs.close_inode(inode_id) {
+            self.clear_data(&mut fs);
+            fs.dealloc_inode(inode_id);
+        }
     }
 }
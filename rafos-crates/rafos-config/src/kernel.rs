@@ -37,6 +37,12 @@ pub const USER_HEAP_PTR: usize = LOW_MAX_VA - PAGE_SIZE * 2 + 1;
 /// 
 pub const USER_STACK_BASE: usize = LOW_MAX_VA + 1;
 
+/// Maximum blocks kept in the block cache of easy-fs.
+pub const BLOCK_CACHE_CAPACITY: usize = 256;
+
+/// Default maximum ready tasks in an `Executor`, beyond which spawning fails.
+pub const EXECUTOR_CAPACITY: usize = 4096;
+
 /// Default maximum file descriptor limit.
 pub const DEFAULT_FD_LIMIT: usize = 0x100;
 
@@ -0,0 +1,74 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll},
+};
+
+use super::{MutexGuard, WaitQueue};
+
+/// A condition variable, which is used together with a [`crate::Mutex`].
+pub struct Condvar {
+    /// Increased by every notification, so a waiter knows whether it is notified.
+    seq: AtomicUsize,
+    waiters: WaitQueue,
+}
+
+impl Condvar {
+    /// Creates a condition variable.
+    pub const fn new() -> Self {
+        Self { seq: AtomicUsize::new(0), waiters: WaitQueue::new() }
+    }
+
+    /// Unlocks the mutex and waits for a notification, then locks the mutex again.
+    ///
+    /// The wakeups may be spurious, so the condition must be checked again.
+    pub async fn wait<'a, T: ?Sized>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        let mutex = MutexGuard::mutex(&guard);
+        let seq = self.seq.load(Ordering::Acquire);
+        drop(guard);
+        Wait { condvar: self, seq }.await;
+        mutex.lock().await
+    }
+
+    /// Wakes a coroutine waiting for the condition variable.
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        self.waiters.wake_one();
+    }
+
+    /// Wakes all the coroutines waiting for the condition variable.
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        self.waiters.wake_all();
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parks the coroutine until the sequence changes.
+struct Wait<'a> {
+    condvar: &'a Condvar,
+    seq: usize,
+}
+
+impl Future for Wait<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.condvar.seq.load(Ordering::Acquire) != self.seq {
+            return Poll::Ready(());
+        }
+        self.condvar.waiters.register(cx.waker());
+        // A notification may come before registering.
+        if self.condvar.seq.load(Ordering::Acquire) != self.seq {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
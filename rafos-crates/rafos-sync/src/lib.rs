@@ -0,0 +1,200 @@
+//! This crate defines the synchronization primitives for coroutines.
+//!
+//! Instead of spinning, a coroutine which can't go on parks itself by registering the
+//! `Waker` of its task, and it is woken by the `Waker` when it may go on. They are usable in
+//! both the kernel and the user runtimes.
+
+#![cfg_attr(not(test), no_std)]
+#![deny(missing_docs)]
+
+extern crate alloc;
+
+mod condvar;
+mod mpsc;
+mod mutex;
+mod notify;
+pub mod oneshot;
+mod rwlock;
+mod semaphore;
+mod wait_queue;
+
+pub use condvar::Condvar;
+pub use mpsc::{channel, Receiver, Recv, Sender};
+pub use mutex::{Mutex, MutexGuard, MutexLock};
+pub use notify::{Notified, Notify};
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use semaphore::{Semaphore, SemaphoreAcquire, SemaphorePermit};
+pub use wait_queue::WaitQueue;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{boxed::Box, vec::Vec};
+    use core::{
+        future::Future,
+        pin::Pin,
+        sync::atomic::{AtomicUsize, Ordering},
+        task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+    };
+
+    static WAKES: AtomicUsize = AtomicUsize::new(0);
+
+    fn waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(core::ptr::null(), &VTABLE)
+        }
+        fn wake(_: *const ()) {
+            WAKES.fetch_add(1, Ordering::Relaxed);
+        }
+        fn drop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake, drop);
+        unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE)) }
+    }
+
+    fn poll<F: Future + ?Sized>(fut: Pin<&mut F>) -> Poll<F::Output> {
+        fut.poll(&mut Context::from_waker(&waker()))
+    }
+
+    #[test]
+    fn channel_test() {
+        let (tx, mut rx) = channel();
+        let tx2 = tx.clone();
+        {
+            let mut recv = rx.recv();
+            assert!(poll(Pin::new(&mut recv)).is_pending());
+        }
+        tx.send(1).unwrap();
+        tx2.send(2).unwrap();
+        assert_eq!(poll(Pin::new(&mut rx.recv())), Poll::Ready(Some(1)));
+        assert_eq!(rx.try_recv(), Some(2));
+        drop(tx);
+        drop(tx2);
+        assert_eq!(poll(Pin::new(&mut rx.recv())), Poll::Ready(None));
+
+        let (tx, rx) = channel();
+        drop(rx);
+        assert_eq!(tx.send(3), Err(3));
+    }
+
+    #[test]
+    fn mutex_test() {
+        let mutex = Mutex::new(0);
+        let guard = mutex.try_lock().unwrap();
+        let mut lock = Box::pin(mutex.lock());
+        assert!(poll(lock.as_mut()).is_pending());
+        let wakes = WAKES.load(Ordering::Relaxed);
+        drop(guard);
+        assert!(WAKES.load(Ordering::Relaxed) > wakes);
+        match poll(lock.as_mut()) {
+            Poll::Ready(mut guard) => *guard += 1,
+            Poll::Pending => panic!("the mutex should be unlocked"),
+        }
+        drop(lock);
+        assert!(!mutex.is_locked());
+        assert_eq!(mutex.into_inner(), 1);
+    }
+
+    #[test]
+    fn condvar_test() {
+        let mutex = Mutex::new(Vec::new());
+        let condvar = Condvar::new();
+        let mut wait = Box::pin(async {
+            let mut guard = mutex.lock().await;
+            while guard.is_empty() {
+                guard = condvar.wait(guard).await;
+            }
+            guard.pop()
+        });
+        assert!(poll(wait.as_mut()).is_pending());
+        assert!(!mutex.is_locked());
+        mutex.try_lock().unwrap().push(7);
+        condvar.notify_one();
+        assert_eq!(poll(wait.as_mut()), Poll::Ready(Some(7)));
+    }
+
+    #[test]
+    fn semaphore_test() {
+        let semaphore = Semaphore::new(1);
+        let permit = semaphore.try_acquire().unwrap();
+        assert!(semaphore.try_acquire().is_none());
+        let mut acquire = Box::pin(semaphore.acquire());
+        assert!(poll(acquire.as_mut()).is_pending());
+        drop(permit);
+        assert!(poll(acquire.as_mut()).is_ready());
+        assert_eq!(semaphore.available_permits(), 1);
+    }
+
+    #[test]
+    fn rwlock_test() {
+        let lock = RwLock::new(0);
+        let read1 = lock.try_read().unwrap();
+        let read2 = lock.try_read().unwrap();
+        assert!(lock.try_write().is_none());
+        let mut write = Box::pin(lock.write());
+        assert!(poll(write.as_mut()).is_pending());
+        drop(read1);
+        assert!(poll(write.as_mut()).is_pending());
+        let wakes = WAKES.load(Ordering::Relaxed);
+        drop(read2);
+        assert!(WAKES.load(Ordering::Relaxed) > wakes);
+        let mut guard = match poll(write.as_mut()) {
+            Poll::Ready(guard) => guard,
+            Poll::Pending => panic!("the lock should be released"),
+        };
+        *guard += 1;
+        assert!(lock.try_read().is_none());
+        let mut read = Box::pin(lock.read());
+        assert!(poll(read.as_mut()).is_pending());
+        drop(guard);
+        match poll(read.as_mut()) {
+            Poll::Ready(guard) => assert_eq!(*guard, 1),
+            Poll::Pending => panic!("the lock should be released"),
+        };
+    }
+
+    #[test]
+    fn notify_test() {
+        let notify = Notify::new();
+        notify.notify_one();
+        assert!(poll(Pin::new(&mut notify.notified())).is_ready());
+        assert!(!notify.try_notified());
+
+        let mut first = Box::pin(notify.notified());
+        let mut second = Box::pin(notify.notified());
+        assert!(poll(first.as_mut()).is_pending());
+        assert!(poll(second.as_mut()).is_pending());
+        notify.notify_one();
+        assert!(poll(first.as_mut()).is_ready());
+        assert!(poll(second.as_mut()).is_pending());
+        notify.notify_all();
+        assert!(poll(second.as_mut()).is_ready());
+        assert!(!notify.try_notified());
+
+        // A dropped waiter passes its notification on.
+        let mut dropped = Box::pin(notify.notified());
+        let mut next = Box::pin(notify.notified());
+        assert!(poll(dropped.as_mut()).is_pending());
+        assert!(poll(next.as_mut()).is_pending());
+        notify.notify_one();
+        drop(dropped);
+        assert!(poll(next.as_mut()).is_ready());
+    }
+
+    #[test]
+    fn oneshot_test() {
+        let (tx, mut rx) = oneshot::channel();
+        assert!(poll(Pin::new(&mut rx)).is_pending());
+        let wakes = WAKES.load(Ordering::Relaxed);
+        tx.send(5).unwrap();
+        assert!(WAKES.load(Ordering::Relaxed) > wakes);
+        assert_eq!(poll(Pin::new(&mut rx)), Poll::Ready(Ok(5)));
+
+        let (tx, mut rx) = oneshot::channel::<i32>();
+        drop(tx);
+        assert_eq!(poll(Pin::new(&mut rx)), Poll::Ready(Err(oneshot::RecvError)));
+
+        let (tx, rx) = oneshot::channel();
+        drop(rx);
+        assert_eq!(tx.send(6), Err(6));
+    }
+}
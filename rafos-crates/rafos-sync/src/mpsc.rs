@@ -0,0 +1,124 @@
+use alloc::{collections::VecDeque, sync::Arc};
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+};
+use spin::Mutex;
+
+struct Chan<T> {
+    queue: Mutex<VecDeque<T>>,
+    /// The waker of the receiver.
+    waker: Mutex<Option<Waker>>,
+    senders: AtomicUsize,
+    /// Whether the receiver is dropped.
+    closed: Mutex<bool>,
+}
+
+impl<T> Chan<T> {
+    fn wake(&self) {
+        if let Some(waker) = self.waker.lock().take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Creates an unbounded multi-producer, single-consumer channel.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let chan = Arc::new(Chan {
+        queue: Mutex::new(VecDeque::new()),
+        waker: Mutex::new(None),
+        senders: AtomicUsize::new(1),
+        closed: Mutex::new(false),
+    });
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+/// The sending half of the channel, which can be cloned.
+pub struct Sender<T> {
+    chan: Arc<Chan<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends the value, which is given back if the receiver is dropped.
+    pub fn send(&self, value: T) -> Result<(), T> {
+        let closed = self.chan.closed.lock();
+        if *closed {
+            return Err(value);
+        }
+        self.chan.queue.lock().push_back(value);
+        drop(closed);
+        self.chan.wake();
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.senders.fetch_add(1, Ordering::Relaxed);
+        Self { chan: self.chan.clone() }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    /// The receiver is woken when the last sender is dropped, so it sees the channel closed.
+    fn drop(&mut self) {
+        if self.chan.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.chan.wake();
+        }
+    }
+}
+
+/// The receiving half of the channel.
+pub struct Receiver<T> {
+    chan: Arc<Chan<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Receives a value, returns `None` if the channel is empty and all the senders are
+    /// dropped.
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv { receiver: self }
+    }
+
+    /// Receives a value if there is one.
+    pub fn try_recv(&mut self) -> Option<T> {
+        self.chan.queue.lock().pop_front()
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        if let Some(value) = self.try_recv() {
+            return Poll::Ready(Some(value));
+        }
+        *self.chan.waker.lock() = Some(cx.waker().clone());
+        // A value may be sent, or the last sender dropped, before registering.
+        if let Some(value) = self.try_recv() {
+            return Poll::Ready(Some(value));
+        }
+        if self.chan.senders.load(Ordering::Acquire) == 0 {
+            return Poll::Ready(self.try_recv());
+        }
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        *self.chan.closed.lock() = true;
+        self.chan.queue.lock().clear();
+    }
+}
+
+/// The future returned by [`Receiver::recv`].
+pub struct Recv<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T> Future for Recv<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.receiver.poll_recv(cx)
+    }
+}
@@ -0,0 +1,148 @@
+use core::{
+    cell::UnsafeCell,
+    future::Future,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll},
+};
+
+use super::WaitQueue;
+
+/// A mutual exclusion lock, which parks the coroutines waiting for it.
+pub struct Mutex<T: ?Sized> {
+    locked: AtomicBool,
+    waiters: WaitQueue,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    /// Creates an unlocked mutex.
+    pub const fn new(data: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            waiters: WaitQueue::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Consumes the mutex, returns the inner data.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Locks the mutex, the coroutine is parked until the mutex is unlocked.
+    pub fn lock(&self) -> MutexLock<'_, T> {
+        MutexLock { mutex: self, parked: false }
+    }
+
+    /// Locks the mutex if it is unlocked.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| MutexGuard { mutex: self })
+    }
+
+    /// Returns true if the mutex is locked.
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    /// Unlocks the mutex, and wakes a coroutine waiting for it.
+    ///
+    /// # Safety
+    /// The mutex must be locked by a [`MutexGuard`] which has been forgotten, and the data
+    /// must not be accessed through that guard any more.
+    pub unsafe fn force_unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+        self.waiters.wake_one();
+    }
+
+    /// Returns a mutable reference to the data, no lock is needed.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+/// The future returned by [`Mutex::lock`].
+pub struct MutexLock<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+    /// Whether the coroutine has registered in the queue.
+    parked: bool,
+}
+
+impl<'a, T: ?Sized> Future for MutexLock<'a, T> {
+    type Output = MutexGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(guard) = self.mutex.try_lock() {
+            self.parked = false;
+            return Poll::Ready(guard);
+        }
+        self.mutex.waiters.register(cx.waker());
+        // The mutex may be unlocked before registering.
+        match self.mutex.try_lock() {
+            Some(guard) => {
+                self.parked = false;
+                Poll::Ready(guard)
+            }
+            None => {
+                self.parked = true;
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexLock<'_, T> {
+    /// The wakeup may have been taken by this coroutine, so it is passed on.
+    fn drop(&mut self) {
+        if self.parked {
+            self.mutex.waiters.wake_one();
+        }
+    }
+}
+
+/// The guard which unlocks the [`Mutex`] when dropped.
+pub struct MutexGuard<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+}
+
+impl<'a, T: ?Sized> MutexGuard<'a, T> {
+    /// The mutex locked by this guard.
+    pub fn mutex(guard: &Self) -> &'a Mutex<T> {
+        guard.mutex
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        unsafe { self.mutex.force_unlock() };
+    }
+}
@@ -0,0 +1,132 @@
+use alloc::{collections::VecDeque, sync::Arc};
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll, Waker},
+};
+use spin::Mutex;
+
+/// A coroutine waiting for a [`Notify`].
+struct Waiter {
+    notified: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+struct Inner {
+    /// A notification which is stored because there was no waiter.
+    permit: bool,
+    waiters: VecDeque<Arc<Waiter>>,
+}
+
+/// Notifies the coroutines waiting for an event.
+///
+/// If there is no waiter, [`Notify::notify_one`] stores a permit, so the next
+/// [`Notify::notified`] completes at once.
+pub struct Notify {
+    inner: Mutex<Inner>,
+}
+
+impl Notify {
+    /// Creates a `Notify` without a permit.
+    pub const fn new() -> Self {
+        Self {
+            inner: Mutex::new(Inner { permit: false, waiters: VecDeque::new() }),
+        }
+    }
+
+    /// Waits for a notification.
+    pub fn notified(&self) -> Notified<'_> {
+        Notified { notify: self, waiter: None, done: false }
+    }
+
+    /// Takes the stored permit, returns false if there is none.
+    pub fn try_notified(&self) -> bool {
+        core::mem::take(&mut self.inner.lock().permit)
+    }
+
+    /// Notifies the earliest waiter, or stores a permit if there is none.
+    pub fn notify_one(&self) {
+        let mut inner = self.inner.lock();
+        match inner.waiters.pop_front() {
+            Some(waiter) => {
+                drop(inner);
+                Self::wake(&waiter);
+            }
+            None => inner.permit = true,
+        }
+    }
+
+    /// Notifies all the current waiters, no permit is stored.
+    pub fn notify_all(&self) {
+        let waiters = core::mem::take(&mut self.inner.lock().waiters);
+        waiters.iter().for_each(Self::wake);
+    }
+
+    fn wake(waiter: &Arc<Waiter>) {
+        waiter.notified.store(true, Ordering::Release);
+        if let Some(waker) = waiter.waker.lock().take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The future returned by [`Notify::notified`].
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    waiter: Option<Arc<Waiter>>,
+    done: bool,
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(waiter) = &self.waiter {
+            *waiter.waker.lock() = Some(cx.waker().clone());
+            if waiter.notified.load(Ordering::Acquire) {
+                self.done = true;
+                return Poll::Ready(());
+            }
+            return Poll::Pending;
+        }
+        let mut inner = self.notify.inner.lock();
+        if core::mem::take(&mut inner.permit) {
+            drop(inner);
+            self.done = true;
+            return Poll::Ready(());
+        }
+        let waiter = Arc::new(Waiter {
+            notified: AtomicBool::new(false),
+            waker: Mutex::new(Some(cx.waker().clone())),
+        });
+        inner.waiters.push_back(waiter.clone());
+        drop(inner);
+        self.waiter = Some(waiter);
+        Poll::Pending
+    }
+}
+
+impl Drop for Notified<'_> {
+    /// A waiter which is dropped leaves the queue, and a notification it has taken is
+    /// passed on.
+    fn drop(&mut self) {
+        let Some(waiter) = self.waiter.take() else { return };
+        if self.done {
+            return;
+        }
+        let mut inner = self.notify.inner.lock();
+        if waiter.notified.load(Ordering::Acquire) {
+            drop(inner);
+            self.notify.notify_one();
+        } else {
+            inner.waiters.retain(|w| !Arc::ptr_eq(w, &waiter));
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll},
+};
+
+use super::WaitQueue;
+
+/// A counting semaphore, which parks the coroutines waiting for permits.
+pub struct Semaphore {
+    permits: AtomicUsize,
+    waiters: WaitQueue,
+}
+
+impl Semaphore {
+    /// Creates a semaphore with `permits` permits.
+    pub const fn new(permits: usize) -> Self {
+        Self {
+            permits: AtomicUsize::new(permits),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    /// The number of available permits.
+    pub fn available_permits(&self) -> usize {
+        self.permits.load(Ordering::Relaxed)
+    }
+
+    /// Acquires a permit, the coroutine is parked until there is one.
+    pub fn acquire(&self) -> SemaphoreAcquire<'_> {
+        SemaphoreAcquire { semaphore: self, parked: false }
+    }
+
+    /// Acquires a permit if there is one.
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        let mut permits = self.permits.load(Ordering::Relaxed);
+        while permits > 0 {
+            match self.permits.compare_exchange_weak(permits, permits - 1, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => return Some(SemaphorePermit { semaphore: self }),
+                Err(current) => permits = current,
+            }
+        }
+        None
+    }
+
+    /// Adds `n` permits, and wakes the coroutines waiting for them.
+    pub fn add_permits(&self, n: usize) {
+        self.permits.fetch_add(n, Ordering::Release);
+        for _ in 0..n {
+            if !self.waiters.wake_one() {
+                break;
+            }
+        }
+    }
+}
+
+/// The future returned by [`Semaphore::acquire`].
+pub struct SemaphoreAcquire<'a> {
+    semaphore: &'a Semaphore,
+    /// Whether the coroutine has registered in the queue.
+    parked: bool,
+}
+
+impl<'a> Future for SemaphoreAcquire<'a> {
+    type Output = SemaphorePermit<'a>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(permit) = self.semaphore.try_acquire() {
+            self.parked = false;
+            return Poll::Ready(permit);
+        }
+        self.semaphore.waiters.register(cx.waker());
+        // The permits may be added before registering.
+        match self.semaphore.try_acquire() {
+            Some(permit) => {
+                self.parked = false;
+                Poll::Ready(permit)
+            }
+            None => {
+                self.parked = true;
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for SemaphoreAcquire<'_> {
+    /// The wakeup may have been taken by this coroutine, so it is passed on.
+    fn drop(&mut self) {
+        if self.parked {
+            self.semaphore.waiters.wake_one();
+        }
+    }
+}
+
+/// The permit which is given back to the [`Semaphore`] when dropped.
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+}
+
+impl SemaphorePermit<'_> {
+    /// Forgets the permit, so it is never given back.
+    pub fn forget(self) {
+        core::mem::forget(self);
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        self.semaphore.add_permits(1);
+    }
+}
@@ -0,0 +1,44 @@
+use alloc::collections::VecDeque;
+use core::task::Waker;
+use spin::Mutex;
+
+/// The wakers of the coroutines waiting for something.
+///
+/// A woken coroutine must check again whether it can go on, because the wakeups may be
+/// spurious.
+pub struct WaitQueue {
+    wakers: Mutex<VecDeque<Waker>>,
+}
+
+impl WaitQueue {
+    /// Creates an empty queue.
+    pub const fn new() -> Self {
+        Self { wakers: Mutex::new(VecDeque::new()) }
+    }
+
+    /// Registers the waker, unless it is already in the queue.
+    pub fn register(&self, waker: &Waker) {
+        let mut wakers = self.wakers.lock();
+        if !wakers.iter().any(|w| w.will_wake(waker)) {
+            wakers.push_back(waker.clone());
+        }
+    }
+
+    /// Wakes the earliest registered waker, returns false if there is none.
+    pub fn wake_one(&self) -> bool {
+        let waker = self.wakers.lock().pop_front();
+        waker.map(Waker::wake).is_some()
+    }
+
+    /// Wakes all the registered wakers.
+    pub fn wake_all(&self) {
+        let wakers = core::mem::take(&mut *self.wakers.lock());
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+impl Default for WaitQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -13,25 +13,46 @@ pub use stdio::*;
 pub enum SyscallId {
     #[arguments(args = "fd")]
     Dup = 24,
+    #[arguments(args = "olddirfd, oldpath_ptr, newdirfd, newpath_ptr, flags")]
+    Linkat = 37,
     #[arguments(args = "path_ptr, flag_bits")]
     Open = 56,
     #[arguments(args = "fd")]
     Close = 57,
-    #[arguments(args = "pipe_ptr")]
+    #[arguments(args = "pipe_ptr, flags")]
     Pipe = 59,
+    #[arguments(args = "fd, offset, whence")]
+    Lseek = 62,
     #[arguments(args = "fd, buf_ptr, buf_len")]
     Read = 63,
     #[arguments(args = "fd, buf_ptr, buf_len")]
     Write = 64,
+    #[arguments(args = "fd, buf_ptr, buf_len, offset")]
+    Pread = 67,
+    #[arguments(args = "fd, buf_ptr, buf_len, offset")]
+    Pwrite = 68,
+    #[arguments(args = "fd, stat_ptr")]
+    Fstat = 80,
+    Sync = 81,
+    #[arguments(args = "fd")]
+    Fsync = 82,
     #[arguments(args = "exit_code")]
     Exit = 93,
     Yield = 124,
     #[arguments(args = "time_ptr, tz")]
     GetTime = 169,
     GetPid = 172,
+    #[arguments(args = "brk")]
+    Brk = 214,
+    #[arguments(args = "start, len")]
+    Munmap = 215,
     Fork = 220,
     #[arguments(args = "path_ptr, args_ptr")]
     Exec = 221,
+    #[arguments(args = "start, len, prot, flags, fd, off")]
+    Mmap = 222,
+    #[arguments(args = "start, len, prot")]
+    Mprotect = 226,
     #[arguments(args = "pid, exit_code_ptr")]
     WaitPid = 260,
     #[arguments(args = "path_ptr")]
@@ -51,6 +72,8 @@ pub enum SyscallId {
     ClaimExtInt = 603,
     #[arguments(args = "device_id, enable")]
     SetExtIntEnable = 604,
+    #[arguments(args = "msgbuf")]
+    RegisterMsgBuf = 605,
     #[arguments(args = "entry, arg")]
     ThreadCreate = 1000,
     GetTid = 1001,
@@ -77,4 +100,6 @@ pub enum SyscallId {
     Accept = 1201,
     #[arguments(args = "count")]
     UintrTest = 1203,
+    #[arguments(args = "hart, stats_ptr")]
+    ExecutorStats = 1300,
 }
@@ -65,10 +65,14 @@ impl DelayUs<u32> for Delay {
     }
 }
 
-trait DelayUs<T> {
+/// Blocking delay in microseconds.
+pub trait DelayUs<T> {
+    /// Blocks for at least `us` microseconds.
     fn delay_us(&mut self, us: T);
 }
 
-trait DelayMs<T> {
-    fn delay_ms(&mut self, us: T);
+/// Blocking delay in milliseconds.
+pub trait DelayMs<T> {
+    /// Blocks for at least `ms` milliseconds.
+    fn delay_ms(&mut self, ms: T);
 }
\ No newline at end of file
@@ -16,11 +16,18 @@ pub trait Driver: Send + Sync + 'static {
     ///   or chaining multiple timers together.
     fn now(&self) -> u64;
 
+    /// Arms the alarm at `timestamp` in ticks, which replaces the previous one.
+    ///
+    /// When the alarm fires, the driver MUST call [`crate::on_alarm`]. The alarm may fire
+    /// later than `timestamp`, but never earlier. If `timestamp` has passed, it fires as soon
+    /// as possible.
+    fn set_alarm(&self, timestamp: u64);
 }
 
 extern "Rust" {
     fn _time_now() -> u64;
 
+    fn _time_set_alarm(timestamp: u64);
 }
 
 /// See [`Driver::now`]
@@ -28,6 +35,11 @@ pub fn now() -> u64 {
     unsafe { _time_now() }
 }
 
+/// See [`Driver::set_alarm`]
+pub fn set_alarm(timestamp: u64) {
+    unsafe { _time_set_alarm(timestamp) }
+}
+
 /// Set the time Driver implementation.
 ///
 /// See the module documentation for an example.
@@ -40,5 +52,10 @@ macro_rules! time_driver_impl {
         fn _time_now() -> u64 {
             <$t as $crate::driver::Driver>::now(&$name)
         }
+
+        #[no_mangle]
+        fn _time_set_alarm(timestamp: u64) {
+            <$t as $crate::driver::Driver>::set_alarm(&$name, timestamp)
+        }
     };
 }
\ No newline at end of file
@@ -2,20 +2,26 @@
 //! 
 //! 
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![allow(unused)]
 
+extern crate alloc;
+
 mod delay;
 pub mod driver;
 mod duration;
 mod instant;
+mod timer;
+mod timer_queue;
 
 
 
 
-pub use delay::{block_for, Delay};
+pub use delay::{block_for, Delay, DelayMs, DelayUs};
 pub use duration::Duration;
 pub use instant::Instant;
+pub use timer::{Ticker, Timer};
+pub use timer_queue::on_alarm;
 
 /// Ticks per second of the global timebase.
 ///
@@ -0,0 +1,78 @@
+use alloc::sync::Arc;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use super::{timer_queue, Duration, Instant};
+
+/// A future which completes at the given [`Instant`].
+///
+/// The task awaiting it is woken by the timer interrupt, instead of polling it.
+pub struct Timer {
+    expires_at: Instant,
+    entry: Option<Arc<timer_queue::Entry>>,
+}
+
+impl Timer {
+    /// Expires at `expires_at`.
+    pub fn at(expires_at: Instant) -> Self {
+        Self { expires_at, entry: None }
+    }
+
+    /// Expires after `duration` from now.
+    pub fn after(duration: Duration) -> Self {
+        Self::at(Instant::now() + duration)
+    }
+}
+
+impl Future for Timer {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if Instant::now() >= self.expires_at {
+            return Poll::Ready(());
+        }
+        let registered = self.entry.as_ref().is_some_and(|entry| entry.update_waker(cx.waker()));
+        if !registered {
+            let entry = timer_queue::register(self.expires_at.as_ticks(), cx.waker());
+            self.entry = Some(entry);
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        if let Some(entry) = self.entry.take() {
+            entry.cancel();
+        }
+    }
+}
+
+/// Expires periodically, without drifting when the task is late to await it.
+pub struct Ticker {
+    expires_at: Instant,
+    duration: Duration,
+}
+
+impl Ticker {
+    /// Expires every `duration`, the first time is after `duration` from now.
+    pub fn every(duration: Duration) -> Self {
+        Self { expires_at: Instant::now() + duration, duration }
+    }
+
+    /// Restarts the period from now.
+    pub fn reset(&mut self) {
+        self.expires_at = Instant::now() + self.duration;
+    }
+
+    /// Returns the [`Timer`] of the next period.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Timer {
+        let timer = Timer::at(self.expires_at);
+        self.expires_at += self.duration;
+        timer
+    }
+}
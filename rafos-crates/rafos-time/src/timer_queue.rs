@@ -0,0 +1,214 @@
+//! A hierarchical timer wheel, which wakes the tasks of expired timers.
+//!
+//! The wheel ticks once per millisecond. A timer whose deadline differs from the current
+//! tick first in the `l`-th group of [`SLOT_BITS`] bits is kept in level `l`, and is moved
+//! to a lower level when the current tick reaches its slot. Timers further than the
+//! highest level are kept in an overflow list.
+
+use alloc::{sync::Arc, vec::Vec};
+use core::task::Waker;
+use spin::Mutex;
+
+use crate::{driver, TICK_HZ};
+
+/// The driver ticks per tick of the wheel.
+const RESOLUTION: u64 = TICK_HZ / 1_000;
+
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const LEVELS: usize = 4;
+
+/// A registered timer.
+pub(crate) struct Entry {
+    /// The deadline in ticks of the wheel.
+    deadline: u64,
+    /// The waker of the task awaiting the timer, which is taken when the timer expires or is
+    /// dropped.
+    waker: Mutex<Option<Waker>>,
+}
+
+impl Entry {
+    /// Replaces the waker, returns false if the timer has expired.
+    pub(crate) fn update_waker(&self, waker: &Waker) -> bool {
+        let mut current = self.waker.lock();
+        match current.as_mut() {
+            Some(current) => {
+                if !current.will_wake(waker) {
+                    *current = waker.clone();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forgets the waker, so the timer never wakes its task.
+    pub(crate) fn cancel(&self) {
+        self.waker.lock().take();
+    }
+}
+
+struct TimerQueue {
+    /// The current tick of the wheel.
+    now: u64,
+    levels: [[Vec<Arc<Entry>>; SLOTS]; LEVELS],
+    overflow: Vec<Arc<Entry>>,
+    /// The armed alarm in ticks of the wheel.
+    alarm: u64,
+}
+
+static TIMER_QUEUE: Mutex<TimerQueue> = Mutex::new(TimerQueue::new());
+
+impl TimerQueue {
+    const fn new() -> Self {
+        Self {
+            now: 0,
+            levels: [const { [const { Vec::new() }; SLOTS] }; LEVELS],
+            overflow: Vec::new(),
+            alarm: u64::MAX,
+        }
+    }
+
+    /// Inserts the entry into the wheel, or into `expired` if its deadline has passed.
+    fn insert(&mut self, entry: Arc<Entry>, expired: &mut Vec<Arc<Entry>>) {
+        if entry.deadline <= self.now {
+            expired.push(entry);
+            return;
+        }
+        let level = ((entry.deadline ^ self.now).ilog2() / SLOT_BITS) as usize;
+        if level >= LEVELS {
+            self.overflow.push(entry);
+        } else {
+            let slot = (entry.deadline >> (SLOT_BITS * level as u32)) as usize & (SLOTS - 1);
+            self.levels[level][slot].push(entry);
+        }
+    }
+
+    /// The next tick when some timers expire or move to a lower level.
+    fn next_event(&self) -> Option<u64> {
+        let mut next = None;
+        for (level, slots) in self.levels.iter().enumerate() {
+            let shift = SLOT_BITS * level as u32;
+            let current = (self.now >> shift) as usize & (SLOTS - 1);
+            if let Some(slot) = (current + 1..SLOTS).find(|&slot| !slots[slot].is_empty()) {
+                let base = self.now >> (shift + SLOT_BITS) << (shift + SLOT_BITS);
+                let event = base + ((slot as u64) << shift);
+                next = Some(next.map_or(event, |next: u64| next.min(event)));
+            }
+        }
+        if !self.overflow.is_empty() {
+            let shift = SLOT_BITS * LEVELS as u32;
+            let event = ((self.now >> shift) + 1) << shift;
+            next = Some(next.map_or(event, |next: u64| next.min(event)));
+        }
+        next
+    }
+
+    /// Advances the wheel to `now`, and collects the expired timers into `expired`.
+    fn advance(&mut self, now: u64, expired: &mut Vec<Arc<Entry>>) {
+        while let Some(event) = self.next_event().filter(|&event| event <= now) {
+            self.now = event;
+            let shift = SLOT_BITS * LEVELS as u32;
+            if event & ((1 << shift) - 1) == 0 {
+                for entry in core::mem::take(&mut self.overflow) {
+                    self.insert(entry, expired);
+                }
+            }
+            for level in (0..LEVELS).rev() {
+                let shift = SLOT_BITS * level as u32;
+                if event & ((1 << shift) - 1) == 0 {
+                    let slot = (event >> shift) as usize & (SLOTS - 1);
+                    for entry in core::mem::take(&mut self.levels[level][slot]) {
+                        self.insert(entry, expired);
+                    }
+                }
+            }
+        }
+        self.now = self.now.max(now);
+    }
+
+    /// Arms the alarm at the next event, if it is earlier than the armed one.
+    fn arm(&mut self) {
+        if let Some(event) = self.next_event() {
+            if event < self.alarm {
+                self.alarm = event;
+                driver::set_alarm(event * RESOLUTION);
+            }
+        }
+    }
+}
+
+/// The current tick of the wheel.
+fn now() -> u64 {
+    driver::now() / RESOLUTION
+}
+
+/// Wakes the tasks of expired timers.
+fn wake(expired: Vec<Arc<Entry>>) {
+    for entry in expired {
+        if let Some(waker) = entry.waker.lock().take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Registers a timer which expires at `expires_at` in driver ticks, and wakes `waker` then.
+pub(crate) fn register(expires_at: u64, waker: &Waker) -> Arc<Entry> {
+    let entry = Arc::new(Entry {
+        deadline: expires_at.div_ceil(RESOLUTION),
+        waker: Mutex::new(Some(waker.clone())),
+    });
+    let mut expired = Vec::new();
+    let mut queue = TIMER_QUEUE.lock();
+    queue.advance(now(), &mut expired);
+    queue.insert(entry.clone(), &mut expired);
+    queue.arm();
+    drop(queue);
+    wake(expired);
+    entry
+}
+
+/// Wakes the tasks of expired timers, and arms the alarm for the next ones.
+///
+/// This must be called by the time driver when the alarm fires.
+pub fn on_alarm() {
+    let mut expired = Vec::new();
+    let mut queue = TIMER_QUEUE.lock();
+    queue.advance(now(), &mut expired);
+    queue.alarm = u64::MAX;
+    queue.arm();
+    drop(queue);
+    wake(expired);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(deadline: u64) -> Arc<Entry> {
+        Arc::new(Entry { deadline, waker: Mutex::new(None) })
+    }
+
+    #[test]
+    fn wheel_test() {
+        let mut queue = TimerQueue::new();
+        let mut expired = Vec::new();
+        queue.advance(100, &mut expired);
+        let deadlines = [101, 163, 164, 5_000, 300_000, 1 << 30, 1 << 40];
+        for deadline in deadlines {
+            queue.insert(entry(deadline), &mut expired);
+        }
+        queue.insert(entry(100), &mut expired);
+        assert!(expired.len() == 1);
+        expired.clear();
+        for deadline in deadlines {
+            assert!(queue.next_event().unwrap() <= deadline);
+            queue.advance(deadline - 1, &mut expired);
+            assert!(expired.is_empty());
+            queue.advance(deadline, &mut expired);
+            assert!(expired.len() == 1 && expired[0].deadline == deadline);
+            expired.clear();
+        }
+        assert!(queue.next_event().is_none());
+    }
+}
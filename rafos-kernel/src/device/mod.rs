@@ -1,10 +1,16 @@
 mod net;
-// pub mod plic;
+#[cfg(feature = "virtio-blk")]
+pub mod plic;
 pub mod ramfs;
+#[cfg(not(feature = "virtio-blk"))]
 pub use ramfs::BLOCK_DEVICE;
 
 #[cfg(feature = "board_qemu")]
 mod virtio_bus;
+#[cfg(feature = "virtio-blk")]
+mod virtio_blk;
+#[cfg(feature = "virtio-blk")]
+pub use virtio_blk::BLOCK_DEVICE;
 
 pub use net::NET_DEVICE;
 
@@ -1,4 +1,4 @@
-use crate::net::net_interrupt_handler;
+use super::BLOCK_DEVICE;
 use rv_plic::{Priority, PLIC};
 
 pub const PLIC_BASE: usize = 0xc00_0000;
@@ -19,6 +19,7 @@ pub fn get_context(hart_id: usize, mode: char) -> usize {
 
 #[cfg(feature = "board_qemu")]
 pub fn init() {
+    Plic::set_priority(7, Priority::lowest());
     Plic::set_priority(8, Priority::lowest());
 }
 
@@ -32,6 +33,7 @@ pub fn init() {
 #[cfg(feature = "board_qemu")]
 pub fn init_hart(hart_id: usize) {
     let context = get_context(hart_id, 'S');
+    Plic::enable(context, 7);
     Plic::enable(context, 8);
     Plic::set_threshold(context, Priority::any());
 }
@@ -56,13 +58,14 @@ pub fn handle_external_interrupt(hart_id: usize) {
     while let Some(irq) = Plic::claim(context) {
         match irq {
             #[cfg(feature = "board_qemu")]
-            8 => {
-                net_interrupt_handler(irq);
+            7 => {
+                BLOCK_DEVICE.handle_irq();
             }
+            // The net device is polled by smoltcp.
+            #[cfg(feature = "board_qemu")]
+            8 => {}
             #[cfg(feature = "board_axu15eg")]
-            2 | 3 | 4 | 5 => {
-                net_interrupt_handler(irq);
-            }
+            2 | 3 | 4 | 5 => {}
             _ => {
                 warn!("[PLIC]: irq {:?} not supported!", irq);
             }
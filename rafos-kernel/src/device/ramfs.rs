@@ -1,11 +1,11 @@
 use core::slice;
 
-use alloc::sync::Arc;
+use alloc::{boxed::Box, sync::Arc};
 use spin::Lazy;
-use easy_fs::BlockDevice;
-
+use easy_fs::{BlockDevice, BlockFuture};
 
 
+#[cfg(not(feature = "virtio-blk"))]
 pub static BLOCK_DEVICE: Lazy<Arc<dyn BlockDevice>> = Lazy::new(|| Arc::new(RamFS::new()));
 
 pub struct RamFS(usize);
@@ -36,6 +36,15 @@ impl BlockDevice for RamFS {
         target_slice.copy_from_slice(&buf)
     }
 
+    /// The memory copy completes immediately.
+    fn read_block_async<'a>(&'a self, block_id: usize, buf: &'a mut [u8]) -> BlockFuture<'a> {
+        Box::pin(async move { self.read_block(block_id, buf) })
+    }
+
+    fn write_block_async<'a>(&'a self, block_id: usize, buf: &'a [u8]) -> BlockFuture<'a> {
+        Box::pin(async move { self.write_block(block_id, buf) })
+    }
+
     fn handle_irq(&self) {
         unimplemented!();
     }
@@ -0,0 +1,149 @@
+use crate::device::virtio_bus::VirtioHal;
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc};
+use asyncc::{task_from_waker, wake_task, TaskRef};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use easy_fs::{BlockDevice, BlockFuture};
+use spin::{Lazy, Mutex};
+use virtio_drivers::{BlkResp, RespStatus, VirtIOBlk, VirtIOHeader};
+
+/// The virtio-mmio slot next to the net device, whose PLIC source is 7.
+const VIRTIO_BLK_ADDR: usize = 0x10007000;
+
+pub static BLOCK_DEVICE: Lazy<Arc<dyn BlockDevice>> = Lazy::new(|| Arc::new(VirtIOBlock::new()));
+
+/// The state of a submitted request.
+#[derive(Default)]
+struct Request {
+    done: bool,
+    /// The coroutine waiting for the request.
+    task: Option<TaskRef>,
+}
+
+/// A virtio block device whose requests complete by interrupts.
+pub struct VirtIOBlock {
+    blk: Mutex<VirtIOBlk<'static, VirtioHal>>,
+    /// The submitted requests indexed by token.
+    requests: Mutex<BTreeMap<u16, Request>>,
+}
+
+impl VirtIOBlock {
+    pub fn new() -> Self {
+        let blk =
+            VirtIOBlk::<VirtioHal>::new(unsafe { &mut *(VIRTIO_BLK_ADDR as *mut VirtIOHeader) })
+                .expect("can't create blk device by virtio");
+        Self {
+            blk: Mutex::new(blk),
+            requests: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Marks the used requests as done, and wakes the coroutines waiting for them.
+    ///
+    /// The request may complete before its future is polled, so the entry is created here
+    /// if it does not exist.
+    fn complete_used(&self) {
+        let mut blk = self.blk.lock();
+        let mut requests = self.requests.lock();
+        while let Ok(token) = blk.pop_used() {
+            let request = requests.entry(token).or_default();
+            request.done = true;
+            if let Some(task) = request.task.take() {
+                wake_task(task);
+            }
+        }
+    }
+}
+
+/// The future of a submitted request, which owns the response written by the device.
+struct BlockRequest<'a> {
+    device: &'a VirtIOBlock,
+    token: u16,
+    resp: Box<BlkResp>,
+    done: bool,
+}
+
+impl<'a> BlockRequest<'a> {
+    fn read(device: &'a VirtIOBlock, block_id: usize, buf: &'a mut [u8]) -> Self {
+        let mut resp = Box::new(BlkResp::default());
+        let token = unsafe { device.blk.lock().read_block_nb(block_id, buf, &mut resp) }
+            .expect("Error when reading VirtIOBlk");
+        Self { device, token, resp, done: false }
+    }
+
+    fn write(device: &'a VirtIOBlock, block_id: usize, buf: &'a [u8]) -> Self {
+        let mut resp = Box::new(BlkResp::default());
+        let token = unsafe { device.blk.lock().write_block_nb(block_id, buf, &mut resp) }
+            .expect("Error when writing VirtIOBlk");
+        Self { device, token, resp, done: false }
+    }
+
+    /// Returns true if the request is done, otherwise `task` is recorded to be woken.
+    fn try_complete(&mut self, task: Option<TaskRef>) -> bool {
+        let mut requests = self.device.requests.lock();
+        let request = requests.entry(self.token).or_default();
+        if request.done {
+            requests.remove(&self.token);
+            assert!(self.resp.status() == RespStatus::Ok, "VirtIOBlk request failed");
+            self.done = true;
+        } else {
+            request.task = task;
+        }
+        self.done
+    }
+
+    /// Polls the device until the request completes.
+    fn spin(&mut self) {
+        while !self.done {
+            self.device.complete_used();
+            self.try_complete(None);
+        }
+    }
+}
+
+impl Drop for BlockRequest<'_> {
+    /// The device may still write the buffer and `resp`, so they can't be released before
+    /// the request completes.
+    fn drop(&mut self) {
+        self.spin();
+    }
+}
+
+impl Future for BlockRequest<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let task = task_from_waker(cx.waker());
+        if self.try_complete(Some(task)) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl BlockDevice for VirtIOBlock {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        BlockRequest::read(self, block_id, buf).spin();
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        BlockRequest::write(self, block_id, buf).spin();
+    }
+
+    fn read_block_async<'a>(&'a self, block_id: usize, buf: &'a mut [u8]) -> BlockFuture<'a> {
+        Box::pin(BlockRequest::read(self, block_id, buf))
+    }
+
+    fn write_block_async<'a>(&'a self, block_id: usize, buf: &'a [u8]) -> BlockFuture<'a> {
+        Box::pin(BlockRequest::write(self, block_id, buf))
+    }
+
+    fn handle_irq(&self) {
+        self.blk.lock().ack_interrupt();
+        self.complete_used();
+    }
+}
@@ -85,6 +85,8 @@ impl From<KernelError> for Errno {
             KernelError::FDNotFound => Errno::EBADF,
             KernelError::VMANotFound | KernelError::VMAAllocFailed => Errno::ENOMEM,
             KernelError::VMAFailedIO => Errno::EACCES,
+            KernelError::SyscallUnsupported(_) => Errno::ENOSYS,
+            KernelError::FDOutOfBound => Errno::EMFILE,
             
             // TODO
             _ => Errno::EINVAL,
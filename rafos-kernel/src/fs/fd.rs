@@ -35,6 +35,12 @@ impl FDManager {
         fd_manager
     }
 
+    /// Closes all the file descriptors.
+    pub fn clear(&mut self) {
+        self.list.clear();
+        self.recycled.clear();
+    }
+
     /// Returns the shared reference of a [`File`].
     pub fn get(&self, fd: usize) -> KernelResult<Arc<dyn File>> {
         if fd >= self.list.len() || self.list[fd].is_none() {
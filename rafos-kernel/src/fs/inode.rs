@@ -1,13 +1,16 @@
-use super::File;
+use super::{File, FileType, SeekWhence, Stat};
+use errno::Errno;
 use crate::device::BLOCK_DEVICE;
+use config::BLOCK_CACHE_CAPACITY;
 use ubuf::UserBuffer;
 use alloc::{
     sync::Arc,
     vec::Vec,
 };
 use spin::{Mutex, Lazy};
+use time::{Duration, Ticker};
 use bitflags::*;
-use easy_fs::{EasyFileSystem, Inode};
+use easy_fs::{DiskInodeType, EasyFileSystem, Inode};
 
 pub struct OSInode {
     readable: bool,
@@ -45,7 +48,7 @@ impl OSInode {
 }
 
 pub static ROOT_INODE: Lazy<Arc<Inode>> = Lazy::new(|| {
-    let efs = EasyFileSystem::open(BLOCK_DEVICE.clone());
+    let efs = EasyFileSystem::open(BLOCK_DEVICE.clone(), BLOCK_CACHE_CAPACITY);
     Arc::new(EasyFileSystem::root_inode(&efs))
 });
 
@@ -65,6 +68,8 @@ bitflags! {
         const RDWR = 1 << 1;
         const CREATE = 1 << 9;
         const TRUNC = 1 << 10;
+        /// Only used by pipes, whose reads and writes return `EAGAIN` instead of blocking.
+        const NONBLOCK = 1 << 11;
     }
 }
 
@@ -72,7 +77,7 @@ impl OpenFlags {
     /// Do not check validity for simplicity
     /// Return (readable, writable)
     pub fn read_write(&self) -> (bool, bool) {
-        if self.is_empty() {
+        if self.bits() & (Self::WRONLY | Self::RDWR).bits() == 0 {
             (true, false)
         } else if self.contains(Self::WRONLY) {
             (false, true)
@@ -82,29 +87,60 @@ impl OpenFlags {
     }
 }
 
-pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
+/// Opens a file by path, which is resolved from the root directory.
+///
+/// A directory can only be opened as read-only.
+pub fn open_file(path: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
     let (readable, writable) = flags.read_write();
-    if flags.contains(OpenFlags::CREATE) {
-        if let Some(inode) = ROOT_INODE.find(name) {
-            // clear size
-            inode.clear();
-            Some(Arc::new(OSInode::new(readable, writable, inode)))
-        } else {
-            // create file
-            ROOT_INODE
-                .create(name)
-                .map(|inode| Arc::new(OSInode::new(readable, writable, inode)))
+    let inode = match ROOT_INODE.find(path) {
+        Some(inode) if inode.is_dir() => {
+            if writable || flags.intersects(OpenFlags::CREATE | OpenFlags::TRUNC) {
+                return None;
+            }
+            inode
         }
-    } else {
-        ROOT_INODE.find(name).map(|inode| {
-            if flags.contains(OpenFlags::TRUNC) {
+        Some(inode) => {
+            if flags.intersects(OpenFlags::CREATE | OpenFlags::TRUNC) {
+                // clear size
                 inode.clear();
             }
-            Arc::new(OSInode::new(readable, writable, inode))
-        })
+            inode
+        }
+        // create file
+        None if flags.contains(OpenFlags::CREATE) => ROOT_INODE.create(path)?,
+        None => return None,
+    };
+    Some(Arc::new(OSInode::new(readable, writable, inode)))
+}
+
+/// Writes back all dirty blocks of the file system.
+pub fn sync_all() {
+    ROOT_INODE.fsync();
+}
+
+/// How often [`flusher`] writes back the dirty blocks.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Writes back the dirty blocks every [`FLUSH_INTERVAL`], so they survive a power off.
+pub async fn flusher() -> i32 {
+    let mut ticker = Ticker::every(FLUSH_INTERVAL);
+    loop {
+        ticker.next().await;
+        sync_all();
     }
 }
 
+/// Creates a hard link `new_path` to the regular file `old_path`.
+pub fn link_file(old_path: &str, new_path: &str) -> Result<(), isize> {
+    if ROOT_INODE.find(old_path).is_none() {
+        return Err(-(Errno::ENOENT as isize));
+    }
+    if ROOT_INODE.find(new_path).is_some() {
+        return Err(-(Errno::EEXIST as isize));
+    }
+    ROOT_INODE.link(old_path, new_path).map(|_| ()).ok_or(-(Errno::EPERM as isize))
+}
+
 impl File for OSInode {
     fn readable(&self) -> bool {
         self.readable
@@ -125,21 +161,64 @@ impl File for OSInode {
         }
         Ok(total_read_size)
     }
+    /// Writes at the cursor, stops at a short write, e.g. the disk is full.
     fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
         let mut inner = self.inner.lock();
         let mut total_write_size = 0usize;
         for slice in &buf.inner {
             let write_size = inner.inode.write_at(inner.offset, *slice);
-            assert_eq!(write_size, slice.len());
             inner.offset += write_size;
             total_write_size += write_size;
+            if write_size < slice.len() {
+                break;
+            }
         }
         Ok(total_write_size)
     }
-    fn awrite(&self, buf: UserBuffer, pid: usize, key: usize) -> Result<usize, isize> {
-        unimplemented!()
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, isize> {
+        let inner = self.inner.lock();
+        Ok(inner.inode.read_at(offset, buf))
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, isize> {
+        let inner = self.inner.lock();
+        Ok(inner.inode.write_at(offset, buf))
+    }
+    fn seek(&self, offset: isize, whence: SeekWhence) -> Result<usize, isize> {
+        let mut inner = self.inner.lock();
+        let base = match whence {
+            SeekWhence::Set => 0,
+            SeekWhence::Cur => inner.offset,
+            SeekWhence::End => inner.inode.size(),
+        };
+        let new_offset = base as isize + offset;
+        if new_offset < 0 {
+            return Err(-(Errno::EINVAL as isize));
+        }
+        inner.offset = new_offset as usize;
+        Ok(inner.offset)
+    }
+    fn stat(&self) -> Result<Stat, isize> {
+        let stat = self.inner.lock().inode.stat();
+        let file_type = match stat.type_ {
+            DiskInodeType::File => FileType::Regular,
+            DiskInodeType::Directory => FileType::Directory,
+        };
+        Ok(Stat {
+            file_type,
+            size: stat.size as usize,
+            ino: stat.ino as usize,
+            mode: stat.mode as u32,
+            nlink: stat.nlink as u32,
+            uid: stat.uid,
+            gid: stat.gid,
+            blocks: stat.blocks as usize,
+            atime: stat.atime,
+            mtime: stat.mtime,
+            ctime: stat.ctime,
+        })
     }
-    fn aread(&self, buf: UserBuffer, cid: usize, pid: usize, key: usize) -> Result<usize, isize> {
-        unimplemented!()
+    fn fsync(&self) -> Result<(), isize> {
+        self.inner.lock().inode.fsync();
+        Ok(())
     }
 }
\ No newline at end of file
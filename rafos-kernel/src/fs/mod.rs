@@ -1,4 +1,4 @@
-// mod pipe;
+mod pipe;
 pub mod stdio;
 pub mod inode;
 pub mod fd;
@@ -7,42 +7,114 @@ pub use inode::*;
 pub use stdio::*;
 pub use fd::*;
 
+use errno::Errno;
 use ubuf::UserBuffer;
-use core::{
-    future::Future,
-    pin::Pin,
-    task::{Context, Poll},
-};
 
 pub trait File: Send + Sync {
     fn readable(&self) -> bool;
     fn writable(&self) -> bool;
     fn read(&self, buf: UserBuffer) -> Result<usize, isize>;
     fn write(&self, buf: UserBuffer) -> Result<usize, isize>;
-    fn awrite(&self, buf: UserBuffer, pid: usize, key: usize) -> Result<usize, isize>;
-    fn aread(&self, buf: UserBuffer, cid: usize, pid: usize, key: usize) -> Result<usize, isize>;
+    /// Writes in the background, which is unsupported by default.
+    fn awrite(&self, _buf: UserBuffer, _pid: usize, _key: usize) -> Result<usize, isize> {
+        Err(-(Errno::ENOSYS as isize))
+    }
+    /// Reads in the background, which is unsupported by default.
+    fn aread(&self, _buf: UserBuffer, _cid: usize, _pid: usize, _key: usize) -> Result<usize, isize> {
+        Err(-(Errno::ENOSYS as isize))
+    }
+    /// Reads at `offset` without moving the cursor, which is unsupported by the streams.
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize, isize> {
+        Err(-(Errno::ESPIPE as isize))
+    }
+    /// Writes at `offset` without moving the cursor, which is unsupported by the streams.
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize, isize> {
+        Err(-(Errno::ESPIPE as isize))
+    }
+    /// Moves the cursor, and returns the new offset from the start of file.
+    fn seek(&self, _offset: isize, _whence: SeekWhence) -> Result<usize, isize> {
+        Err(-(Errno::ESPIPE as isize))
+    }
+    /// Returns the status of file.
+    fn stat(&self) -> Result<Stat, isize>;
+    /// Writes back the cached data of file, which has nothing to do without a cache.
+    fn fsync(&self) -> Result<(), isize> {
+        Err(-(Errno::EINVAL as isize))
+    }
 }
 
-// pub use pipe::{make_pipe, Pipe};
-// pub use stdio::{Stdin, Stdout};
+/// The `whence` argument of `lseek`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekWhence {
+    /// The offset is set to `offset` bytes.
+    Set,
+    /// The offset is set to its current location plus `offset` bytes.
+    Cur,
+    /// The offset is set to the size of the file plus `offset` bytes.
+    End,
+}
 
-pub struct ReadHelper(usize);
+impl TryFrom<usize> for SeekWhence {
+    type Error = isize;
 
-impl ReadHelper {
-    pub fn new() -> Self {
-        Self(0)
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Set),
+            1 => Ok(Self::Cur),
+            2 => Ok(Self::End),
+            _ => Err(-(Errno::EINVAL as isize)),
+        }
     }
 }
 
-impl Future for ReadHelper {
-    type Output = ();
+/// The type of file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    Regular,
+    Directory,
+    CharDevice,
+    Fifo,
+}
 
-    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.0 += 1;
-        if (self.0 & 1) == 1 {
-            return Poll::Pending;
-        } else {
-            return Poll::Ready(());
+/// The status of file returned by [`File::stat`].
+#[derive(Debug, Clone, Copy)]
+pub struct Stat {
+    pub file_type: FileType,
+    /// Size in bytes.
+    pub size: usize,
+    /// Inode number, 0 for the files not backed by easy-fs.
+    pub ino: usize,
+    /// Permission bits.
+    pub mode: u32,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// Number of 512-byte blocks allocated.
+    pub blocks: usize,
+    /// Timestamps in seconds.
+    pub atime: u64,
+    pub mtime: u64,
+    pub ctime: u64,
+}
+
+impl Stat {
+    /// Creates the status of a file without inode metadata.
+    pub fn new(file_type: FileType, size: usize) -> Self {
+        Self {
+            file_type,
+            size,
+            ino: 0,
+            mode: 0o666,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            blocks: 0,
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
         }
     }
 }
+
+pub use pipe::{make_pipe, Pipe};
+// pub use stdio::{Stdin, Stdout};
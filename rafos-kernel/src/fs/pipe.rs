@@ -1,33 +1,65 @@
-use super::File;
-use crate::fs::ReadHelper;
-use crate::mm::UserBuffer;
-use crate::task::suspend_current_and_run_next;
-use alloc::boxed::Box;
+use super::{File, FileType, Stat};
+use ubuf::UserBuffer;
+use errno::Errno;
 use alloc::sync::{Arc, Weak};
 use spin::Mutex;
+use sync::WaitQueue;
+
+use crate::task::{current_process, current_task};
 
 #[derive(Clone)]
 pub struct Pipe {
     readable: bool,
     writable: bool,
+    /// Whether the reads and writes return `EAGAIN` without parking the caller.
+    nonblock: bool,
     buffer: Arc<Mutex<PipeRingBuffer>>,
 }
 
 impl Pipe {
-    pub fn read_end_with_buffer(buffer: Arc<Mutex<PipeRingBuffer>>) -> Self {
+    pub fn read_end_with_buffer(buffer: Arc<Mutex<PipeRingBuffer>>, nonblock: bool) -> Self {
         Self {
             readable: true,
             writable: false,
+            nonblock,
             buffer,
         }
     }
-    pub fn write_end_with_buffer(buffer: Arc<Mutex<PipeRingBuffer>>) -> Self {
+    pub fn write_end_with_buffer(buffer: Arc<Mutex<PipeRingBuffer>>, nonblock: bool) -> Self {
         Self {
             readable: false,
             writable: true,
+            nonblock,
             buffer,
         }
     }
+
+    /// Parks the calling coroutine in `waiters` unless the pipe is nonblocking, it is resumed
+    /// when the other end reads, writes or is closed.
+    ///
+    /// The syscall runs with the interrupts disabled, so it can't wait for the other end,
+    /// which may be on the same hart. It returns `EAGAIN` and the coroutine tries again
+    /// after it is resumed.
+    fn park(&self, waiters: &WaitQueue) -> isize {
+        if !self.nonblock {
+            if let Some((_, waker)) = current_process().as_ref().and_then(current_task) {
+                waiters.register(&waker);
+            }
+        }
+        -(Errno::EAGAIN as isize)
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let ring_buffer = self.buffer.lock();
+        if self.readable {
+            ring_buffer.writers.wake_all();
+        }
+        if self.writable {
+            ring_buffer.readers.wake_all();
+        }
+    }
 }
 
 const RING_BUFFER_SIZE: usize = 4096;
@@ -46,6 +78,10 @@ pub struct PipeRingBuffer {
     status: RingBufferStatus,
     write_end: Option<Weak<Pipe>>,
     read_end: Option<Weak<Pipe>>,
+    /// The coroutines waiting for bytes to read.
+    readers: WaitQueue,
+    /// The coroutines waiting for room to write.
+    writers: WaitQueue,
 }
 
 impl PipeRingBuffer {
@@ -57,6 +93,8 @@ impl PipeRingBuffer {
             status: RingBufferStatus::EMPTY,
             write_end: None,
             read_end: None,
+            readers: WaitQueue::new(),
+            writers: WaitQueue::new(),
         }
     }
     pub fn set_write_end(&mut self, write_end: &Arc<Pipe>) {
@@ -110,86 +148,63 @@ impl PipeRingBuffer {
 }
 
 /// Return (read_end, write_end)
-pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
+pub fn make_pipe(nonblock: bool) -> (Arc<Pipe>, Arc<Pipe>) {
     let buffer = Arc::new(Mutex::new(PipeRingBuffer::new()));
-    let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
-    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
+    let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone(), nonblock));
+    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone(), nonblock));
     buffer.lock().set_write_end(&write_end);
     buffer.lock().set_read_end(&read_end);
     (read_end, write_end)
 }
 
 impl File for Pipe {
+    /// Reads the available bytes.
+    ///
+    /// Returns `EAGAIN` if the pipe is empty but the write end is still open, the caller is
+    /// parked until there are bytes to read.
     fn read(&self, buf: UserBuffer) -> Result<usize, isize> {
         assert!(self.readable);
-        let mut buf_iter = buf.into_iter();
-        let mut read_size = 0usize;
-        loop {
-            let mut ring_buffer = self.buffer.lock();
-            let loop_read = ring_buffer.available_read();
-            if loop_read == 0 {
-                if ring_buffer.all_write_ends_closed() {
-                    return Ok(read_size);
-                }
-                drop(ring_buffer);
-                // debug!("[pipe sync read] suspend");
-                suspend_current_and_run_next();
-                continue;
-            }
-            // read at most loop_read bytes
-            for _ in 0..loop_read {
-                if let Some(byte_ref) = buf_iter.next() {
-                    unsafe {
-                        *byte_ref = ring_buffer.read_byte();
-                    }
-                    read_size += 1;
-                } else {
-                    return Ok(read_size);
-                }
+        let mut ring_buffer = self.buffer.lock();
+        let loop_read = ring_buffer.available_read();
+        if loop_read == 0 {
+            if ring_buffer.all_write_ends_closed() {
+                return Ok(0);
             }
-
-            if buf_iter.is_full() {
-                return Ok(read_size);
+            return Err(self.park(&ring_buffer.readers));
+        }
+        let mut read_size = 0usize;
+        // read at most loop_read bytes
+        for byte_ref in buf.into_iter().take(loop_read) {
+            unsafe {
+                *byte_ref = ring_buffer.read_byte();
             }
+            read_size += 1;
         }
+        ring_buffer.writers.wake_all();
+        Ok(read_size)
     }
+    /// Writes the bytes the ring buffer can hold.
+    ///
+    /// Returns `EPIPE` if the read end has been closed, or `EAGAIN` if the pipe is full, the
+    /// caller is parked until there is room to write.
     fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
         assert!(self.writable);
-        let mut buf_iter = buf.into_iter();
+        let mut ring_buffer = self.buffer.lock();
+        if ring_buffer.all_read_ends_closed() {
+            return Err(-(Errno::EPIPE as isize));
+        }
+        let loop_write = ring_buffer.available_write();
+        if loop_write == 0 && buf.len() != 0 {
+            return Err(self.park(&ring_buffer.writers));
+        }
         let mut write_size = 0usize;
-        loop {
-            let mut ring_buffer = self.buffer.lock();
-            let loop_write = ring_buffer.available_write();
-            if loop_write == 0 {
-                debug!("iter ++");
-                if ring_buffer.all_read_ends_closed() {
-                    debug!("pipe readFD closed");
-                    return Ok(write_size);
-                }
-                drop(ring_buffer);
-                suspend_current_and_run_next();
-                continue;
-            }
-            // write at most loop_write bytes
-            for _ in 0..loop_write {
-                if let Some(byte_ref) = buf_iter.next() {
-                    ring_buffer.write_byte(unsafe { *byte_ref });
-                    write_size += 1;
-                } else {
-                    return Ok(write_size);
-                }
-            }
+        // write at most loop_write bytes
+        for byte_ref in buf.into_iter().take(loop_write) {
+            ring_buffer.write_byte(unsafe { *byte_ref });
+            write_size += 1;
         }
-    }
-    fn awrite(&self, buf: UserBuffer, pid: usize, key: usize) -> Result<usize, isize> {
-        let work = Box::pin(awrite_work(self.clone(), buf, pid, key));
-        lib_so::spawn(move || work, 0, 0, lib_so::CoroutineKind::KernSyscall);
-        Ok(0)
-    }
-    fn aread(&self, buf: UserBuffer, cid: usize, pid: usize, key: usize) -> Result<usize, isize> {
-        let work = Box::pin(aread_work(self.clone(), buf, cid, pid, key));
-        lib_so::spawn(move || work, 0, 0, lib_so::CoroutineKind::KernSyscall);
-        Ok(0)
+        ring_buffer.readers.wake_all();
+        Ok(write_size)
     }
 
     fn readable(&self) -> bool {
@@ -199,85 +214,8 @@ impl File for Pipe {
     fn writable(&self) -> bool {
         self.writable
     }
-}
-
-async fn awrite_work(s: Pipe, buf: UserBuffer, pid: usize, key: usize) {
-    assert!(s.writable);
-    let mut buf_iter = buf.into_iter();
-    let mut helper = Box::new(ReadHelper::new());
-    loop {
-        let mut ring_buffer = s.buffer.lock();
-        let loop_write = ring_buffer.available_write();
-        if loop_write == 0 {
-            debug!("iter ++");
-            if ring_buffer.all_read_ends_closed() {
-                debug!("pipe readFD closed");
-                break;
-            }
-            drop(ring_buffer);
-            helper.as_mut().await;
-            continue;
-        }
-        // write at most loop_write bytes
-        for _ in 0..loop_write {
-            if let Some(byte_ref) = buf_iter.next() {
-                ring_buffer.write_byte(unsafe { *byte_ref });
-            } else {
-                break;
-            }
-        }
-        if buf_iter.is_full() {
-            debug!("write complete!");
-            break;
-        }
-    }
-    let async_key = crate::syscall::AsyncKey { pid, key };
-    // 向文件中写完数据之后，需要唤醒内核当中的协程，将管道中的数据写到缓冲区中
-    if let Some(kernel_cid) = crate::syscall::WRMAP.lock().remove(&async_key) {
-        // info!("kernel_cid {}", kernel_cid);
-        lib_so::re_back(kernel_cid, 0);
-    }
-    debug!("pipe write end");
-}
 
-async fn aread_work(s: Pipe, buf: UserBuffer, _cid: usize, pid: usize, key: usize) {
-    let mut buf_iter = buf.into_iter();
-    // let mut read_size = 0usize;
-    let mut helper = Box::new(ReadHelper::new());
-    loop {
-        let mut ring_buffer = s.buffer.lock();
-        let loop_read = ring_buffer.available_read();
-        if loop_read == 0 {
-            debug!("read_size is 0");
-            if ring_buffer.all_write_ends_closed() {
-                break;
-                //return read_size;
-            }
-            drop(ring_buffer);
-            crate::syscall::WRMAP.lock().insert(
-                crate::syscall::AsyncKey { pid, key },
-                lib_so::current_cid(true),
-            );
-            helper.as_mut().await;
-            continue;
-        }
-        debug!("read_size is {}", loop_read);
-        // read at most loop_read bytes
-        for _ in 0..loop_read {
-            if let Some(byte_ref) = buf_iter.next() {
-                unsafe {
-                    *byte_ref = ring_buffer.read_byte();
-                }
-            } else {
-                break;
-            }
-        }
-        if buf_iter.is_full() {
-            debug!("read complete!");
-            break;
-        }
+    fn stat(&self) -> Result<Stat, isize> {
+        Ok(Stat::new(FileType::Fifo, self.buffer.lock().available_read()))
     }
-    // 将读协程加入到回调队列中，使得用户态的协程执行器能够唤醒读协程
-    debug!("read pid is {}", pid);
-    debug!("key is {}", key);
 }
@@ -1,4 +1,6 @@
-use super::File;
+use super::{File, FileType, Stat};
+use alloc::string::String;
+use errno::Errno;
 use ubuf::UserBuffer;
 
 pub struct Stdin;
@@ -8,8 +10,11 @@ pub struct Stdout;
 pub struct Stderr;
 
 impl File for Stdin {
+    /// Reads a character, so `user_buf` must hold exactly one byte.
     fn read(&self, mut user_buf: UserBuffer) -> Result<usize, isize> {
-        assert_eq!(user_buf.len(), 1);
+        if user_buf.len() != 1 {
+            return Err(-(Errno::EINVAL as isize));
+        }
         #[allow(deprecated)]
         let ch = sbi_rt::legacy::console_getchar() as isize;
         if ch < 0 {
@@ -22,12 +27,6 @@ impl File for Stdin {
     fn write(&self, _user_buf: UserBuffer) -> Result<usize, isize> {
         panic!("Cannot write to stdin!");
     }
-    fn awrite(&self, _buf: UserBuffer, _pid: usize, _key: usize) -> Result<usize, isize> {
-        unimplemented!();
-    }
-    fn aread(&self, _buf: UserBuffer, _cid: usize, _pid: usize, _key: usize) -> Result<usize, isize> {
-        unimplemented!();
-    }
 
     fn readable(&self) -> bool {
         true
@@ -36,6 +35,10 @@ impl File for Stdin {
     fn writable(&self) -> bool {
         false
     }
+
+    fn stat(&self) -> Result<Stat, isize> {
+        Ok(Stat::new(FileType::CharDevice, 0))
+    }
 }
 
 impl File for Stdout {
@@ -44,18 +47,10 @@ impl File for Stdout {
     }
     fn write(&self, user_buf: UserBuffer) -> Result<usize, isize> {
         for buffer in &user_buf.inner {
-            console::print!("{}", core::str::from_utf8(buffer).unwrap());
+            console::print!("{}", String::from_utf8_lossy(buffer));
         }
         Ok(user_buf.len())
     }
-    
-    fn awrite(&self, _buf: UserBuffer, _pid: usize, _key: usize) -> Result<usize, isize> {
-        unimplemented!();
-    }
-    
-    fn aread(&self, _buf: UserBuffer, _cid: usize, _pid: usize, _key: usize) -> Result<usize, isize> {
-        unimplemented!();
-    }
 
     fn readable(&self) -> bool {
         false
@@ -64,6 +59,10 @@ impl File for Stdout {
     fn writable(&self) -> bool {
         true
     }
+
+    fn stat(&self) -> Result<Stat, isize> {
+        Ok(Stat::new(FileType::CharDevice, 0))
+    }
 }
 
 
@@ -73,18 +72,10 @@ impl File for Stderr {
     }
     fn write(&self, user_buf: UserBuffer) -> Result<usize, isize> {
         for buffer in &user_buf.inner {
-            log::error!("{}", core::str::from_utf8(buffer).unwrap());
+            log::error!("{}", String::from_utf8_lossy(buffer));
         }
         Ok(user_buf.len())
     }
-    
-    fn awrite(&self, _buf: UserBuffer, _pid: usize, _key: usize) -> Result<usize, isize> {
-        unimplemented!();
-    }
-    
-    fn aread(&self, _buf: UserBuffer, _cid: usize, _pid: usize, _key: usize) -> Result<usize, isize> {
-        unimplemented!();
-    }
 
     fn readable(&self) -> bool {
         false
@@ -93,4 +84,8 @@ impl File for Stderr {
     fn writable(&self) -> bool {
         true
     }
+
+    fn stat(&self) -> Result<Stat, isize> {
+        Ok(Stat::new(FileType::CharDevice, 0))
+    }
 }
\ No newline at end of file
@@ -0,0 +1,81 @@
+/// This mod loads the ELF executable into the user address space.
+///
+
+use mmrv::*;
+use xmas_elf::{
+    header::{self, Class, Machine},
+    program::{self, SegmentData},
+    ElfFile,
+};
+
+use crate::{mm::{MM, VMFlags}, KernelError, KernelResult};
+
+/// Loads the `PT_LOAD` segments of an ELF64 RISC-V executable into the [`MM`].
+///
+/// Sets `entry` as the entry point of the executable, and `start_brk` as the
+/// first page after the last loaded segment.
+///
+/// # Error
+/// - [KernelError::ELFInvalidHeader]: wrong magic number, unsupported class or architecture.
+/// - [KernelError::ELFInvalidSegment]: the segment overflows, is out of the file or overlaps.
+pub fn from_elf(elf_data: &[u8], mm: &mut MM) -> KernelResult {
+    let elf = ElfFile::new(elf_data).map_err(|_| KernelError::ELFInvalidHeader)?;
+    let elf_hdr = elf.header;
+    if elf_hdr.pt1.magic != header::MAGIC
+        || elf_hdr.pt1.class() != Class::SixtyFour
+        || elf_hdr.pt2.machine().as_machine() != Machine::RISC_V
+    {
+        return Err(KernelError::ELFInvalidHeader);
+    }
+    header::sanity_check(&elf).map_err(|_| KernelError::ELFInvalidHeader)?;
+
+    let mut max_end_va = VirtAddr::zero();
+    for ph in elf.program_iter() {
+        if ph.get_type() != Ok(program::Type::Load) {
+            continue;
+        }
+        // The fields are untrusted, so the sums must not overflow.
+        let file_end = ph.offset().checked_add(ph.file_size()).ok_or(KernelError::ELFInvalidSegment)?;
+        let mem_end = ph.virtual_addr().checked_add(ph.mem_size()).ok_or(KernelError::ELFInvalidSegment)?;
+        if ph.file_size() > ph.mem_size() || ph.mem_size() == 0 || file_end > elf_data.len() as u64 {
+            return Err(KernelError::ELFInvalidSegment);
+        }
+        let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
+        let end_va: VirtAddr = (mem_end as usize).into();
+        if end_va <= start_va || !mm.get_vma_range(start_va, end_va)?.is_empty() {
+            return Err(KernelError::ELFInvalidSegment);
+        }
+
+        let mut flags = VMFlags::USER;
+        let ph_flags = ph.flags();
+        if ph_flags.is_read() {
+            flags |= VMFlags::READ;
+        }
+        if ph_flags.is_write() {
+            flags |= VMFlags::WRITE;
+        }
+        if ph_flags.is_execute() {
+            flags |= VMFlags::EXEC;
+        }
+
+        // `.bss` is zero-filled, since the frames are flushed when allocated.
+        let data = match ph.get_data(&elf) {
+            Ok(SegmentData::Undefined(data)) => data,
+            _ => return Err(KernelError::ELFInvalidSegment),
+        };
+        let data = if data.is_empty() { None } else { Some(data) };
+        mm.alloc_write_vma(data, start_va, end_va, flags)?;
+        if end_va > max_end_va {
+            max_end_va = end_va;
+        }
+        log::trace!("LOAD [{:?}, {:?}) => {:?}", start_va, end_va, flags);
+    }
+    if max_end_va == VirtAddr::zero() {
+        return Err(KernelError::ELFInvalidSegment);
+    }
+
+    mm.entry = (elf_hdr.pt2.entry_point() as usize).into();
+    mm.start_brk = Page::ceil(max_end_va).start_address();
+    mm.brk = mm.start_brk;
+    Ok(())
+}
@@ -23,9 +23,15 @@ mod fs;
 mod device;
 mod timer;
 mod task;
+mod loader;
+mod syscall;
 mod trampoline;
+mod trap;
+#[cfg(feature = "kernel-test")]
+mod test;
 
 pub use error::*;
+use alloc::boxed::Box;
 use asyncc::*;
 use mmrv::frame_init;
 
@@ -33,7 +39,6 @@ use core::sync::atomic::{Ordering, AtomicUsize};
 use config::{CPU_NUM, MEMORY_END};
 use mmrv::*;
 
-// use crate::fs::{open_file, OpenFlags};
 
 
 
@@ -51,6 +56,11 @@ pub const TOTAL_BOOT_STACK_SIZE: usize = BOOT_STACK_SIZE * CPU_NUM;
 #[link_section = ".bss.stack"]
 static mut STACK: [u8; TOTAL_BOOT_STACK_SIZE] = [0u8; TOTAL_BOOT_STACK_SIZE];
 
+/// Returns the top of the boot stack of the hart, which `_start` sets to `sp`.
+pub fn boot_stack_top(hart_id: usize) -> usize {
+    unsafe { core::ptr::addr_of!(STACK) as usize + TOTAL_BOOT_STACK_SIZE - hart_id * BOOT_STACK_SIZE }
+}
+
 /// Entry for the first kernel.
 #[naked]
 #[no_mangle]
@@ -126,43 +136,57 @@ pub fn rust_main_init(hart_id: usize) -> ! {
     );
     BOOT_HART.fetch_add(1, Ordering::Relaxed);
     fs::list_apps();
+    timer::init();
     // lkm::init();
     
     // net::init();
     // device::init();
-    // plic::init();
-    // plic::init_hart(hart_id);
-
-
-    // if CPU_NUM > 1 {
-    //     for i in 0..CPU_NUM {
-    //         let boot_hart_cnt = BOOT_HART.load(Ordering::Relaxed);
-    //         if i != hart_id {
-    //             // Starts other harts.
-    //             let ret = sbi_rt::hart_start(i, __entry_others as _, 0);
-    //             assert!(ret.is_ok(), "Failed to shart hart {}", i);
-    //             while BOOT_HART.load(Ordering::Relaxed) == boot_hart_cnt {}
-    //         }
-    //     }
-    // }
+    #[cfg(feature = "virtio-blk")]
+    {
+        device::plic::init();
+        device::plic::init_hart(hart_id);
+        unsafe { riscv::register::sie::set_sext() };
+    }
+    // The first process is spawned before other harts boot, so that they can fetch it.
+    #[cfg(not(feature = "kernel-test"))]
+    let _process = {
+        let file = fs::open_file("shell", fs::OpenFlags::RDONLY).expect("shell not found");
+        task::Process::new(&file.read_all()).unwrap()
+    };
+    #[cfg(feature = "kernel-test")]
+    task::spawn_global(Box::new(test::run()), 0, TaskType::Other).unwrap();
+    task::Process::new_kp(Box::new(fs::flusher()), 0).unwrap();
+
+    if CPU_NUM > 1 {
+        for i in 0..CPU_NUM {
+            let boot_hart_cnt = BOOT_HART.load(Ordering::Relaxed);
+            if i != hart_id {
+                // Starts other harts.
+                let ret = sbi_rt::hart_start(i, __entry_others as _, 0);
+                assert!(ret.is_ok(), "Failed to shart hart {}", i);
+                while BOOT_HART.load(Ordering::Relaxed) == boot_hart_cnt {}
+            }
+        }
+    }
     rust_main(hart_id)
 }
 
 #[no_mangle]
 pub fn rust_main_init_other(hart_id: usize) -> ! {
+    timer::init();
+    #[cfg(feature = "virtio-blk")]
+    {
+        device::plic::init_hart(hart_id);
+        unsafe { riscv::register::sie::set_sext() };
+    }
     BOOT_HART.fetch_add(1, Ordering::Relaxed);
     rust_main(hart_id)
 }
 
-static mut EXECUTOR: Executor = Executor::new();
-
-
+/// Every hart runs the tasks in its own `Executor` and the global one.
 #[no_mangle]
 pub fn rust_main(_hart_id: usize) -> ! {
-    Asyncc::reset(unsafe { &EXECUTOR });
-    // let file = open_file("shell", OpenFlags::RDONLY);
-    // let _process = task::Process::new(&file.unwrap().read_all()).unwrap();
-    // let _process = task::Process::new_kp().unwrap();
+    Asyncc::reset(task::local_executor());
     unsafe {
         Asyncc::set_cause(asyncc::Cause::Finish);
         trampoline::asyncc_entry();
@@ -21,14 +21,12 @@ impl MmapFile {
 
     /// Reads at `off` starting from `self.offset`.
     pub fn read(&self, off: usize, buf: &mut [u8]) -> Option<usize> {
-        // self.file.read_at_off(off + self.offset, buf)
-        todo!()
+        self.file.read_at(off + self.offset, buf).ok()
     }
 
     /// Writes at `off` starting from `self.offset`.
     pub fn write(&self, off: usize, buf: &[u8]) -> Option<usize> {
-        // self.file.write_at_off(off + self.offset, buf)
-        todo!()
+        self.file.write_at(off + self.offset, buf).ok()
     }
 
     /// Split at `off` starting from `self.offset`
@@ -7,14 +7,15 @@ use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
 use core::{fmt, mem::size_of, slice};
 use ubuf::UserBuffer;
 
-use crate::{KernelError, KernelResult};
+use crate::{fs::{File, FileType}, KernelError, KernelResult};
 use config::*;
+use errno::Errno;
 
 pub use file::MmapFile;
 pub use flags::*;
 use vma::VMArea;
 use mmrv::*;
-pub use kernel::KERNEL_SPACE;
+pub use kernel::{kernel_activate, new_kernel, KERNEL_SPACE};
 
 
 
@@ -112,12 +113,15 @@ impl MM {
                 let mut flags = PTEFlags::from(vma.flags);
                 flags.remove(PTEFlags::WRITABLE);
 
-                // map the new vma of child process
-                new_vma.map_all(&mut page_table, flags, false)?;
-                new_vma_list.push(Some(new_vma));
+                // the frames of an inaccessible area stay unmapped, see `VMArea::remap`
+                if vma.flags.intersects(VMFlags::READ | VMFlags::WRITE | VMFlags::EXEC) {
+                    // map the new vma of child process
+                    new_vma.map_all(&mut page_table, flags, false)?;
 
-                // remap the old vma of parent process
-                vma.map_all(&mut self.page_table, flags, false)?;
+                    // remap the old vma of parent process
+                    vma.map_all(&mut self.page_table, flags, false)?;
+                }
+                new_vma_list.push(Some(new_vma));
             } else {
                 new_vma_list.push(None);
             }
@@ -144,6 +148,18 @@ impl MM {
         })
     }
 
+    /// Unmaps all the [`VMArea`]s, whose frames are freed, e.g. when the process exits.
+    ///
+    /// The page table must not be in use.
+    pub fn clear(&mut self) {
+        self.vma_cache = None;
+        self.vma_map.clear();
+        self.vma_recycled.clear();
+        for vma in self.vma_list.drain(..).flatten() {
+            vma.unmap_all(&mut self.page_table).unwrap();
+        }
+    }
+
     /// A warpper for `translate` in `PageTable`.
     pub fn translate(&mut self, va: VirtAddr) -> KernelResult<PhysAddr> {
         self.page_table
@@ -355,6 +371,31 @@ impl MM {
         Ok(v)
     }
 
+    /// Checks that the user areas cover [va, va + len) and allow the access of `flags`.
+    ///
+    /// The kernel writes through the frames, so it must not write a read-only area, whose
+    /// frames may be shared with other processes for copy-on-write.
+    ///
+    /// # Error
+    /// - `EFAULT`: some page is unmapped, or its area is not accessible to the user.
+    pub fn check_user(&mut self, va: VirtAddr, len: usize, flags: VMFlags) -> KernelResult {
+        let fault = KernelError::Errno(Errno::EFAULT);
+        let end_va = VirtAddr::from(va.value().checked_add(len).ok_or(fault)?);
+        let mut start_va = va;
+        while start_va < end_va {
+            start_va = self
+                .get_vma(start_va, |vma, _, _| {
+                    if vma.flags.contains(flags | VMFlags::USER) {
+                        Ok(vma.end_va)
+                    } else {
+                        Err(fault)
+                    }
+                })
+                .map_err(|_| fault)?;
+        }
+        Ok(())
+    }
+
     /// Allocates a frame for mapped page.
     ///
     /// # Argument
@@ -394,13 +435,15 @@ impl MM {
         Ok(())
     }
 
-    /// Allocates a type and writes data to the physical address.
+    /// Allocates a type and writes data to the physical address, which must be writable by
+    /// the user.
     ///
     /// # Argument
     /// - `va`: starting virtual address where the data type locates.
     /// - `data`: reference of data type.
     pub fn alloc_write_type<T: Sized>(&mut self, va: VirtAddr, data: &T) -> KernelResult {
         let size = size_of::<T>();
+        self.check_user(va, size, VMFlags::WRITE)?;
         let end_va = va + size;
         self.alloc_frame_range(va, end_va)?;
         let data = unsafe { slice::from_raw_parts(data as *const T as *const _, size) };
@@ -409,7 +452,7 @@ impl MM {
     }
 
     /// Gets bytes translated with the range of [start_va, start_va + len),
-    /// which might cover several pages.
+    /// which might cover several pages and must be writable by the user.
     ///
     /// The buffer may not be allocated with frames, so new frames will be
     /// allocated for further modifications on this buffer.
@@ -418,6 +461,18 @@ impl MM {
     /// - `va`: starting virtual address
     /// - `len`: total length of the buffer
     pub fn get_buf_mut(&mut self, va: VirtAddr, len: usize) -> KernelResult<UserBuffer> {
+        self.check_user(va, len, VMFlags::WRITE)?;
+        self.translate_buf(va, len)
+    }
+
+    /// Gets bytes like [`MM::get_buf_mut`], which must be readable by the user and are only
+    /// read by the kernel.
+    pub fn get_buf(&mut self, va: VirtAddr, len: usize) -> KernelResult<UserBuffer> {
+        self.check_user(va, len, VMFlags::READ)?;
+        self.translate_buf(va, len)
+    }
+
+    fn translate_buf(&mut self, va: VirtAddr, len: usize) -> KernelResult<UserBuffer> {
         let mut start_va = va;
         let end_va = start_va + len;
         let mut v = Vec::new();
@@ -451,6 +506,7 @@ impl MM {
                 alloc = true;
             }
             if alloc {
+                self.check_user(va, 1, VMFlags::READ)?;
                 frame = self.alloc_frame(va)?;
                 alloc = false;
             }
@@ -512,7 +568,7 @@ pub fn page_range(start_va: VirtAddr, end_va: VirtAddr) -> PageRange {
 #[macro_export]
 macro_rules! read_user {
     ($mm:expr, $addr:expr, $item:expr, $ty:ty) => {{
-        let ubuf = $mm.get_buf_mut($addr, core::mem::size_of::<$ty>())?;
+        let ubuf = $mm.get_buf($addr, core::mem::size_of::<$ty>())?;
         ubuf::read_user_buf!(ubuf, $ty, $item);
         Ok::<(), Errno>(())
     }};
@@ -528,59 +584,62 @@ macro_rules! write_user {
     }};
 }
 
-// /// A helper for [`syscall_interface::SyscallProc::brk`].
-// pub fn do_brk(mm: &mut MM, brk: VirtAddr) -> SyscallResult {
-//     if brk < mm.start_brk {
-//         return Ok(mm.brk.value());
-//     }
-
-//     let new_page = Page::from(brk);
-//     let old_page = Page::from(mm.brk);
-//     if new_page == old_page {
-//         mm.brk = brk;
-//         return Ok(brk.value());
-//     }
-
-//     // Always allow shrinking brk.
-//     if brk < mm.brk {
-//         if do_munmap(
-//             mm,
-//             (new_page + 1).start_address(),
-//             (old_page.number() - new_page.number()) * PAGE_SIZE,
-//         )
-//         .is_err()
-//         {
-//             return Ok(mm.brk.value());
-//         }
-//         mm.brk = brk;
-//         return Ok(mm.brk.value());
-//     }
-
-//     // Check against existing mmap mappings.
-//     if mm.get_vma(brk - 1, |_, _, _| Ok(())).is_ok() {
-//         return Ok(mm.brk.value());
-//     }
-
-//     // Initialize memory area
-//     if mm.brk == mm.start_brk {
-//         mm.add_vma(VMArea::new_lazy(
-//             mm.start_brk,
-//             mm.start_brk + PAGE_SIZE,
-//             VMFlags::USER | VMFlags::READ | VMFlags::WRITE,
-//             None,
-//         )?)?;
-//     }
-
-//     mm.get_vma(mm.start_brk, |vma, _, _| unsafe {
-//         vma.extend(brk);
-//         Ok(())
-//     })
-//     .unwrap();
-//     mm.brk = brk;
-//     Ok(brk.value())
-// }
-
-/// A helper for [`syscall_interface::SyscallProc::munmap`].
+/// A helper for `sys_brk`.
+///
+/// Returns the new program break on success, or the old one on failure.
+/// The heap grows at most to [`MM::mmap_min_addr`].
+pub fn do_brk(mm: &mut MM, brk: VirtAddr) -> KernelResult<usize> {
+    if brk < mm.start_brk || brk > mm.mmap_min_addr() {
+        return Ok(mm.brk.value());
+    }
+
+    let new_page = Page::from(brk);
+    let old_page = Page::from(mm.brk);
+    if new_page == old_page {
+        mm.brk = brk;
+        return Ok(brk.value());
+    }
+
+    // Always allow shrinking brk.
+    if brk < mm.brk {
+        if do_munmap(
+            mm,
+            (new_page + 1).start_address(),
+            (old_page.number() - new_page.number()) * PAGE_SIZE,
+        )
+        .is_err()
+        {
+            return Ok(mm.brk.value());
+        }
+        mm.brk = brk;
+        return Ok(mm.brk.value());
+    }
+
+    // Check against existing mmap mappings.
+    if mm.get_vma(brk - 1, |_, _, _| Ok(())).is_ok() {
+        return Ok(mm.brk.value());
+    }
+
+    // Initialize memory area
+    if mm.brk == mm.start_brk {
+        mm.add_vma(VMArea::new_lazy(
+            mm.start_brk,
+            mm.start_brk + PAGE_SIZE,
+            VMFlags::USER | VMFlags::READ | VMFlags::WRITE,
+            None,
+        )?)?;
+    }
+
+    mm.get_vma(mm.start_brk, |vma, _, _| unsafe {
+        vma.extend(brk);
+        Ok(())
+    })
+    .unwrap();
+    mm.brk = brk;
+    Ok(brk.value())
+}
+
+/// A helper for `sys_munmap`.
 pub fn do_munmap(mm: &mut MM, start: VirtAddr, len: usize) -> KernelResult {
     let len = page_align(len);
     if !start.is_aligned() || len == 0 {
@@ -633,171 +692,184 @@ pub fn do_munmap(mm: &mut MM, start: VirtAddr, len: usize) -> KernelResult {
     Ok(())
 }
 
-// /// A helper for [`syscall_interface::SyscallProc::mprotect`].
-// pub fn do_mprotect(mm: &mut MM, start: VirtAddr, len: usize, prot: MmapProt) -> SyscallResult {
-//     log::trace!("MPROTECT [{:?}, {:?}), {:#?}", start, start + len, prot);
-
-//     let len = page_align(len);
-//     if !start.is_aligned() || len == 0 {
-//         return Err(Errno::EINVAL);
-//     }
-//     let end = start + len;
-
-//     // avoid crashes
-//     mm.vma_cache = None;
-
-//     // search vmas
-//     let vma_range = mm.get_vma_range(start, end)?;
-//     if vma_range.is_empty() {
-//         return Err(Errno::ENOMEM);
-//     }
-
-//     let new_flags = VMFlags::from(prot);
-//     for index in vma_range {
-//         let vma = mm.vma_list[index].as_mut().unwrap();
-
-//         // checks file access
-//         if let Some(file) = &vma.file {
-//             if !file.mprot(prot) {
-//                 return Err(Errno::EACCES);
-//             }
-//         }
-
-//         // checks flag difference
-//         let new_flags = new_flags | vma.flags & !(VMFlags::READ | VMFlags::WRITE | VMFlags::EXEC);
-//         if new_flags == vma.flags {
-//             continue;
-//         }
-
-//         // checks map limit
-//         if (start > vma.start_va || end < vma.end_va) && mm.vma_map.len() + 1 >= MAX_MAP_COUNT {
-//             return Err(Errno::ENOMEM);
-//         }
-
-//         // intersection cases
-//         if vma.start_va >= start && vma.end_va <= end {
-//             vma.flags = new_flags;
-//         } else if vma.start_va < start && vma.end_va > end {
-//             let (mut mid, right) = vma.split(start, end);
-//             mid.as_mut().unwrap().flags = new_flags;
-//             mm.add_vma(mid.unwrap()).unwrap();
-//             mm.add_vma(right.unwrap()).unwrap();
-//         } else if vma.end_va > end {
-//             // vma starting address modified to end
-//             mm.vma_map.remove(&vma.start_va);
-//             let mut left = vma.split(start, end).0.unwrap();
-//             mm.vma_map.insert(vma.start_va, index);
-//             left.flags = new_flags;
-//             mm.add_vma(left).unwrap();
-//         } else {
-//             let mut right = vma.split(start, end).0.unwrap();
-//             right.flags = new_flags;
-//             mm.add_vma(right).unwrap();
-//         }
-//     }
-
-//     Ok(0)
-// }
-
-// /// A helper for [`syscall_interface::SyscallProc::mmap`].
-// ///
-// /// TODO: MAP_SHARED and MAP_PRIVATE
-// pub fn do_mmap(
-//     task: &Task,
-//     hint: VirtAddr,
-//     len: usize,
-//     prot: MmapProt,
-//     flags: MmapFlags,
-//     fd: usize,
-//     off: usize,
-// ) -> SyscallResult {
-//     log::trace!(
-//         "MMAP [{:?}, {:?}) {:#?} {:#?} 0x{:X} 0x{:X}",
-//         hint,
-//         hint + len,
-//         prot,
-//         flags,
-//         fd,
-//         off
-//     );
-
-//     if len == 0
-//         || !hint.is_aligned()
-//         || !(hint + len).is_aligned()
-//         || hint + len > VirtAddr::from(LOW_MAX_VA)
-//         || hint == VirtAddr::zero() && flags.contains(MmapFlags::MAP_FIXED)
-//     {
-//         return Err(Errno::EINVAL);
-//     }
-
-//     let mut mm = task.mm();
-//     if mm.map_count() >= MAX_MAP_COUNT {
-//         return Err(Errno::ENOMEM);
-//     }
-
-//     // Find an available area by kernel.
-//     let anywhere = hint == VirtAddr::zero() && !flags.contains(MmapFlags::MAP_FIXED);
-
-//     // Handle different cases indicated by `MmapFlags`.
-//     if flags.contains(MmapFlags::MAP_ANONYMOUS) {
-//         if fd as isize == -1 && off == 0 {
-//             if let Ok(start) = mm.alloc_vma(hint, hint + len, prot.into(), anywhere, None) {
-//                 return Ok(start.value());
-//             } else {
-//                 return Err(Errno::ENOMEM);
-//             }
-//         }
-//         return Err(Errno::EINVAL);
-//     }
-
-//     // Map to backend file.
-//     if let Ok(file) = task.files().get(fd) {
-//         if !file.is_reg() || !file.read_ready() {
-//             return Err(Errno::EACCES);
-//         }
-//         if let Some(_) = file.seek(off, vfs::SeekWhence::Set) {
-//             if let Ok(start) = mm.alloc_vma(
-//                 hint,
-//                 hint + len,
-//                 prot.into(),
-//                 anywhere,
-//                 Some(Arc::new(MmapFile::new(file, off))),
-//             ) {
-//                 return Ok(start.value());
-//             } else {
-//                 return Err(Errno::ENOMEM);
-//             }
-//         } else {
-//             return Err(Errno::EACCES);
-//         }
-//     } else {
-//         return Err(Errno::EBADF);
-//     }
-
-//     // Invalid arguments or unimplemented cases
-//     // flags contained none of MAP_PRIVATE, MAP_SHARED, or MAP_SHARED_VALIDATE.
-//     // Err(Errno::EINVAL)
-// }
-
-// /* Trap helpers */
-
-// /// A page fault helper for [`crate::trap::user_trap_handler`].
-// ///
-// /// Store page fault might be caused by:
-// /// 1. Frame not allocated yet;
-// /// 2. Unable to write (COW);
-// pub fn do_handle_page_fault(mm: &mut MM, va: VirtAddr, flags: VMFlags) -> KernelResult {
-//     mm.get_vma(va, |vma, pt, _| {
-//         if !vma.flags.contains(flags) {
-//             return Err(KernelError::FatalPageFault);
-//         }
-
-//         let (_, alloc) = vma.alloc_frame(Page::from(va), pt)?;
-
-//         if !alloc {
-//             return Err(KernelError::FatalPageFault);
-//         }
-
-//         Ok(())
-//     })
-// }
\ No newline at end of file
+/// A helper for `sys_mprotect`.
+///
+/// The areas partially covered by the range are split, and the page table entries
+/// of allocated frames are updated with the new flags.
+pub fn do_mprotect(mm: &mut MM, start: VirtAddr, len: usize, prot: MmapProt) -> KernelResult<usize> {
+    log::trace!("MPROTECT [{:?}, {:?}), {:#?}", start, start + len, prot);
+
+    let len = page_align(len + PAGE_SIZE - 1);
+    if !start.is_aligned() || len == 0 {
+        return Err(KernelError::InvalidArgs);
+    }
+    let end = start + len;
+
+    // avoid crashes
+    mm.vma_cache = None;
+
+    // search vmas
+    let vma_range = mm.get_vma_range(start, end)?;
+    if vma_range.is_empty() {
+        return Err(KernelError::VMANotFound);
+    }
+
+    let new_flags = VMFlags::from(prot);
+    for index in vma_range {
+        let vma = mm.vma_list[index].as_mut().unwrap();
+
+        // checks file access
+        if let Some(file) = &vma.file {
+            if !file.mprot(prot) {
+                return Err(KernelError::Errno(Errno::EACCES));
+            }
+        }
+
+        // checks flag difference
+        let new_flags = new_flags | vma.flags & !(VMFlags::READ | VMFlags::WRITE | VMFlags::EXEC);
+        if new_flags == vma.flags {
+            continue;
+        }
+
+        // checks map limit
+        if (start > vma.start_va || end < vma.end_va) && mm.vma_map.len() + 1 >= MAX_MAP_COUNT {
+            return Err(KernelError::VMAAllocFailed);
+        }
+
+        // intersection cases
+        if vma.start_va >= start && vma.end_va <= end {
+            vma.flags = new_flags;
+            vma.remap(&mut mm.page_table)?;
+        } else if vma.start_va < start && vma.end_va > end {
+            let (mid, right) = vma.split(start, end);
+            let mut mid = mid.unwrap();
+            mid.flags = new_flags;
+            mid.remap(&mut mm.page_table)?;
+            mm.add_vma(mid).unwrap();
+            mm.add_vma(right.unwrap()).unwrap();
+        } else if vma.end_va > end {
+            // vma starting address modified to end
+            mm.vma_map.remove(&vma.start_va);
+            let mut left = vma.split(start, end).0.unwrap();
+            mm.vma_map.insert(vma.start_va, index);
+            left.flags = new_flags;
+            left.remap(&mut mm.page_table)?;
+            mm.add_vma(left).unwrap();
+        } else {
+            let mut right = vma.split(start, end).0.unwrap();
+            right.flags = new_flags;
+            right.remap(&mut mm.page_table)?;
+            mm.add_vma(right).unwrap();
+        }
+    }
+
+    Ok(0)
+}
+
+/// A helper for `sys_mmap`.
+///
+/// - `MAP_FIXED`: the mapping is placed exactly at `hint`, discarding the overlapped areas.
+///   Otherwise `hint` is used only if the range is free.
+/// - `MAP_SHARED`: updates are written back to the file when frames are reclaimed.
+/// - `MAP_PRIVATE`: frames are private and never written back.
+///
+/// # Argument
+/// - `file`: the backend file, which must be `None` for `MAP_ANONYMOUS`.
+/// - `off`: offset in the backend file, which must be aligned.
+pub fn do_mmap(
+    mm: &mut MM,
+    hint: VirtAddr,
+    len: usize,
+    prot: MmapProt,
+    flags: MmapFlags,
+    file: Option<Arc<dyn File>>,
+    off: usize,
+) -> KernelResult<usize> {
+    log::trace!(
+        "MMAP [{:?}, {:?}) {:#?} {:#?} 0x{:X}",
+        hint,
+        hint + len,
+        prot,
+        flags,
+        off
+    );
+
+    let len = page_align(len + PAGE_SIZE - 1);
+    if len == 0
+        || !hint.is_aligned()
+        || off % PAGE_SIZE != 0
+        || hint + len > VirtAddr::from(USER_MAX_PAGES * PAGE_SIZE)
+        || hint == VirtAddr::zero() && flags.contains(MmapFlags::MAP_FIXED)
+        || flags.contains(MmapFlags::MAP_SHARED) == flags.contains(MmapFlags::MAP_PRIVATE)
+    {
+        return Err(KernelError::InvalidArgs);
+    }
+
+    if mm.map_count() >= MAX_MAP_COUNT {
+        return Err(KernelError::VMAAllocFailed);
+    }
+
+    // Find an available area by kernel if the hint cannot be used.
+    let anywhere = !flags.contains(MmapFlags::MAP_FIXED)
+        && (hint == VirtAddr::zero() || !mm.get_vma_range(hint, hint + len)?.is_empty());
+
+    let mut vm_flags = VMFlags::from(prot);
+    if flags.contains(MmapFlags::MAP_SHARED) {
+        vm_flags |= VMFlags::SHARED;
+    }
+
+    // Handle different cases indicated by `MmapFlags`.
+    let file = if flags.contains(MmapFlags::MAP_ANONYMOUS) {
+        if file.is_some() || off != 0 {
+            return Err(KernelError::InvalidArgs);
+        }
+        None
+    } else {
+        let file = file.ok_or(KernelError::FDNotFound)?;
+        match file.stat() {
+            Ok(stat) if stat.file_type == FileType::Regular => {}
+            _ => return Err(KernelError::Errno(Errno::ENODEV)),
+        }
+        let mmap_file = MmapFile::new(file, off);
+        if !mmap_file.mprot(prot)
+            || flags.contains(MmapFlags::MAP_PRIVATE) && !mmap_file.mprot(MmapProt::PROT_READ)
+        {
+            return Err(KernelError::Errno(Errno::EACCES));
+        }
+        Some(Arc::new(mmap_file))
+    };
+
+    let start = mm.alloc_vma(hint, hint + len, vm_flags, anywhere, file)?;
+    Ok(start.value())
+}
+
+/* Trap helpers */
+
+/// A page fault helper for [`crate::trap::trap_handler`].
+///
+/// Page fault might be caused by:
+/// 1. Frame not allocated yet (lazy allocation);
+/// 2. Unable to write (COW);
+///
+/// # Argument
+/// - `va`: the faulting virtual address.
+/// - `flags`: the access type, [`VMFlags::READ`], [`VMFlags::WRITE`] or [`VMFlags::EXEC`].
+///
+/// # Error
+/// - [`KernelError::PageUnmapped`]: the address is not mapped by any area.
+/// - [`KernelError::FatalPageFault`]: the access violates the protection of the area.
+pub fn do_handle_page_fault(mm: &mut MM, va: VirtAddr, flags: VMFlags) -> KernelResult {
+    mm.get_vma(va, |vma, pt, _| {
+        if !vma.flags.contains(flags) {
+            return Err(KernelError::FatalPageFault);
+        }
+
+        let (_, alloc) = vma.alloc_frame(Page::from(va), pt)?;
+
+        if !alloc {
+            return Err(KernelError::FatalPageFault);
+        }
+
+        Ok(())
+    })
+}
@@ -139,9 +139,11 @@ impl VMArea {
     }
 
     /// Reclaims the frame by index, writing back to file if before the [`AllocatedFrame`] dropped.
+    ///
+    /// Only shared mappings write back, private mappings discard the updates.
     pub fn reclaim_frame(&mut self, index: usize) -> Option<Arc<AllocatedFrame>> {
         if let Some(frame) = self.frames[index].take() {
-            if self.file.is_some() && Arc::strong_count(&frame) == 1 {
+            if self.file.is_some() && self.flags.contains(VMFlags::SHARED) && Arc::strong_count(&frame) == 1 {
                 // TODO: wirte if dirty
                 self.file
                     .as_ref()
@@ -217,8 +219,43 @@ impl VMArea {
         Ok(())
     }
 
+    /// Updates the page table entries of allocated frames with the flags of this area.
+    ///
+    /// Frames still shared for copy-on-write (COW) are kept read-only. If the area can't be
+    /// read, written or executed, the entries are invalidated, since a valid entry without
+    /// `R`, `W` and `X` points to the next level page table. The frames are kept, and mapped
+    /// again when the access is allowed.
+    pub fn remap(&self, pt: &mut PageTable) -> KernelResult {
+        let accessible = self.flags.intersects(VMFlags::READ | VMFlags::WRITE | VMFlags::EXEC);
+        for (page, frame) in page_range(self.start_va, self.end_va)
+            .range()
+            .zip(self.frames.iter())
+        {
+            if let Some(frame) = frame {
+                if !accessible {
+                    pt.unmap(page);
+                    continue;
+                }
+                let mut flags = PTEFlags::from(self.flags);
+                if Arc::strong_count(frame) > 1 && !self.flags.contains(VMFlags::SHARED) {
+                    flags.remove(PTEFlags::WRITABLE);
+                }
+                pt.map(page, (*frame.as_ref()).clone(), PTEFlags::VALID | flags)
+                    .map_err(|err| {
+                        warn!("{}", err);
+                        KernelError::PageTableInvalid
+                    })?;
+            }
+        }
+        unsafe { sfence_vma_all() };
+        Ok(())
+    }
+
     /// Allocates a frame for mapped page.
     ///
+    /// If the page is mapped read-only for copy-on-write (COW), the frame will be copied
+    /// only if it is still shared by other areas. Shared areas never copy frames.
+    ///
     /// Returns true if a new frame is really allocated.
     pub fn alloc_frame(&mut self, page: Page, pt: &mut PageTable) -> KernelResult<(Frame, bool)> {
         let (pte_pa, mut pte) = pt.create(page).map_err(|_| KernelError::PageTableInvalid)?;
@@ -226,8 +263,11 @@ impl VMArea {
             || (!pte.flags().contains(PTEFlags::WRITABLE) && self.flags.contains(VMFlags::WRITE))
         {
             let index = page.number() - Page::from(self.start_va).number();
+            let shared = self.frames[index]
+                .as_ref()
+                .map_or(false, |frame| Arc::strong_count(frame) > 1);
 
-            let frame = if pte.flags().is_valid() {
+            let frame = if pte.flags().is_valid() && shared && !self.flags.contains(VMFlags::SHARED) {
                 let old = self.get_frame(index, false)?;
                 // we don't drop the old frame immediately, for it can be allocated again as new frame
                 let need_drop = self.reclaim_frame(index);
@@ -237,6 +277,7 @@ impl VMArea {
                 drop(need_drop);
                 new
             } else {
+                // lazy allocation, or the last owner of a COW frame
                 self.get_frame(index, true)?
             };
 
@@ -245,6 +286,7 @@ impl VMArea {
             );
             pte.set_ppn(&frame);
             pte.write(pte_pa);
+            unsafe { sfence_vma_all() };
             return Ok((frame, true));
         }
         Ok((pte.frame(), false))
@@ -364,4 +406,15 @@ impl fmt::Debug for VMArea {
             self.flags
         )
     }
-}
\ No newline at end of file
+}
+
+impl Drop for VMArea {
+    /// Writes back the frames of a shared file mapping when unmapped.
+    fn drop(&mut self) {
+        if self.file.is_some() && self.flags.contains(VMFlags::SHARED) {
+            for index in 0..self.frames.len() {
+                self.reclaim_frame(index);
+            }
+        }
+    }
+}
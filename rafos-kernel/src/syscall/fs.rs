@@ -0,0 +1,224 @@
+use errno::Errno;
+use mmrv::VirtAddr;
+
+use crate::{
+    fs::{link_file, make_pipe, open_file, sync_all, FileType, OpenFlags, SeekWhence},
+    task::current_process,
+    KernelError, KernelResult,
+};
+
+/// The `dirfd` referring to the working directory.
+const AT_FDCWD: usize = -100isize as usize;
+
+/// `struct stat` in Linux on riscv64.
+#[repr(C)]
+struct KStat {
+    st_dev: u64,
+    st_ino: u64,
+    st_mode: u32,
+    st_nlink: u32,
+    st_uid: u32,
+    st_gid: u32,
+    st_rdev: u64,
+    __pad: u64,
+    st_size: i64,
+    st_blksize: u32,
+    __pad2: i32,
+    st_blocks: u64,
+    st_atime_sec: i64,
+    st_atime_nsec: i64,
+    st_mtime_sec: i64,
+    st_mtime_nsec: i64,
+    st_ctime_sec: i64,
+    st_ctime_nsec: i64,
+    __unused: [u32; 2],
+}
+
+/// Converts the error returned by a [`crate::fs::File`] into [`KernelError`].
+fn file_err(err: isize) -> KernelError {
+    KernelError::Errno(Errno::try_from(-err).unwrap_or(Errno::EIO))
+}
+
+pub fn sys_read(fd: usize, buf_ptr: usize, buf_len: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let file = process.fd_table.lock().get(fd)?;
+    if !file.readable() {
+        return Err(KernelError::Errno(Errno::EBADF));
+    }
+    let buf = process.mm.lock().get_buf_mut(VirtAddr::from(buf_ptr), buf_len)?;
+    file.read(buf).map_err(file_err)
+}
+
+pub fn sys_write(fd: usize, buf_ptr: usize, buf_len: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let file = process.fd_table.lock().get(fd)?;
+    if !file.writable() {
+        return Err(KernelError::Errno(Errno::EBADF));
+    }
+    let buf = process.mm.lock().get_buf(VirtAddr::from(buf_ptr), buf_len)?;
+    file.write(buf).map_err(file_err)
+}
+
+pub fn sys_open(path_ptr: usize, flag_bits: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let path = process.mm.lock().get_str(VirtAddr::from(path_ptr))?;
+    let flags = OpenFlags::from_bits(flag_bits as u32).ok_or(KernelError::InvalidArgs)?;
+    let file = open_file(path.as_str(), flags).ok_or(KernelError::Errno(Errno::ENOENT))?;
+    let fd = process.fd_table.lock().push(file)?;
+    Ok(fd)
+}
+
+pub fn sys_close(fd: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    process.fd_table.lock().take(fd)?;
+    Ok(0)
+}
+
+pub fn sys_dup(fd: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let mut fd_table = process.fd_table.lock();
+    let file = fd_table.get(fd)?;
+    fd_table.push(file)
+}
+
+/// Creates a pipe, and writes `[read_fd, write_fd]` to `pipe_ptr`.
+///
+/// Only `OpenFlags::NONBLOCK` is accepted in `flags`.
+pub fn sys_pipe(pipe_ptr: usize, flags: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let flags = OpenFlags::from_bits(flags as u32).ok_or(KernelError::InvalidArgs)?;
+    if !(flags - OpenFlags::NONBLOCK).is_empty() {
+        return Err(KernelError::InvalidArgs);
+    }
+    let (read_end, write_end) = make_pipe(flags.contains(OpenFlags::NONBLOCK));
+    let mut fd_table = process.fd_table.lock();
+    let read_fd = fd_table.push(read_end)?;
+    let write_fd = match fd_table.push(write_end) {
+        Ok(fd) => fd,
+        Err(err) => {
+            fd_table.take(read_fd)?;
+            return Err(err);
+        }
+    };
+    if let Err(err) = process.mm.lock().alloc_write_type(VirtAddr::from(pipe_ptr), &[read_fd, write_fd]) {
+        fd_table.take(read_fd)?;
+        fd_table.take(write_fd)?;
+        return Err(err);
+    }
+    Ok(0)
+}
+
+pub fn sys_lseek(fd: usize, offset: usize, whence: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let file = process.fd_table.lock().get(fd)?;
+    let whence = SeekWhence::try_from(whence).map_err(file_err)?;
+    file.seek(offset as isize, whence).map_err(file_err)
+}
+
+/// Reads at `offset` without moving the cursor of file.
+pub fn sys_pread(fd: usize, buf_ptr: usize, buf_len: usize, offset: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let file = process.fd_table.lock().get(fd)?;
+    if !file.readable() {
+        return Err(KernelError::Errno(Errno::EBADF));
+    }
+    let buf = process.mm.lock().get_buf_mut(VirtAddr::from(buf_ptr), buf_len)?;
+    let mut total_read_size = 0;
+    for slice in buf.inner {
+        let read_size = file.read_at(offset + total_read_size, slice).map_err(file_err)?;
+        total_read_size += read_size;
+        if read_size < slice.len() {
+            break;
+        }
+    }
+    Ok(total_read_size)
+}
+
+/// Writes at `offset` without moving the cursor of file.
+pub fn sys_pwrite(fd: usize, buf_ptr: usize, buf_len: usize, offset: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let file = process.fd_table.lock().get(fd)?;
+    if !file.writable() {
+        return Err(KernelError::Errno(Errno::EBADF));
+    }
+    let buf = process.mm.lock().get_buf(VirtAddr::from(buf_ptr), buf_len)?;
+    let mut total_write_size = 0;
+    for slice in buf.inner {
+        let write_size = file.write_at(offset + total_write_size, slice).map_err(file_err)?;
+        total_write_size += write_size;
+        if write_size < slice.len() {
+            break;
+        }
+    }
+    Ok(total_write_size)
+}
+
+/// Writes the status of file to `stat_ptr`.
+pub fn sys_fstat(fd: usize, stat_ptr: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let file = process.fd_table.lock().get(fd)?;
+    let stat = file.stat().map_err(file_err)?;
+    let file_type = match stat.file_type {
+        FileType::Regular => 0o100000,
+        FileType::Directory => 0o040000,
+        FileType::CharDevice => 0o020000,
+        FileType::Fifo => 0o010000,
+    };
+    let kstat = KStat {
+        st_dev: 0,
+        st_ino: stat.ino as u64,
+        st_mode: file_type | stat.mode,
+        st_nlink: stat.nlink,
+        st_uid: stat.uid,
+        st_gid: stat.gid,
+        st_rdev: 0,
+        __pad: 0,
+        st_size: stat.size as i64,
+        st_blksize: 512,
+        __pad2: 0,
+        st_blocks: stat.blocks as u64,
+        st_atime_sec: stat.atime as i64,
+        st_atime_nsec: 0,
+        st_mtime_sec: stat.mtime as i64,
+        st_mtime_nsec: 0,
+        st_ctime_sec: stat.ctime as i64,
+        st_ctime_nsec: 0,
+        __unused: [0; 2],
+    };
+    process.mm.lock().alloc_write_type(VirtAddr::from(stat_ptr), &kstat)?;
+    Ok(0)
+}
+
+/// Creates a hard link.
+///
+/// There is no working directory yet, so paths are resolved from the root directory and
+/// `dirfd` must be `AT_FDCWD`. No flag is supported.
+pub fn sys_linkat(
+    olddirfd: usize,
+    oldpath_ptr: usize,
+    newdirfd: usize,
+    newpath_ptr: usize,
+    flags: usize,
+) -> KernelResult<usize> {
+    if olddirfd != AT_FDCWD || newdirfd != AT_FDCWD || flags != 0 {
+        return Err(KernelError::Errno(Errno::EINVAL));
+    }
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let old_path = process.mm.lock().get_str(VirtAddr::from(oldpath_ptr))?;
+    let new_path = process.mm.lock().get_str(VirtAddr::from(newpath_ptr))?;
+    link_file(old_path.as_str(), new_path.as_str()).map_err(file_err)?;
+    Ok(0)
+}
+
+/// Writes back all dirty blocks of the file system.
+pub fn sys_sync() -> KernelResult<usize> {
+    sync_all();
+    Ok(0)
+}
+
+pub fn sys_fsync(fd: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let file = process.fd_table.lock().get(fd)?;
+    file.fsync().map_err(file_err)?;
+    Ok(0)
+}
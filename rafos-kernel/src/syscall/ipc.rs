@@ -0,0 +1,68 @@
+use core::sync::atomic::Ordering;
+
+use asyncc::{Message, MsgBuf, MAILBOX_KEY, MSG_PAYLOAD_SIZE};
+use errno::Errno;
+use mmrv::{VirtAddr, PAGE_SIZE};
+
+use crate::{
+    mm::VMFlags,
+    task::{current_process, find_process},
+    KernelError, KernelResult,
+};
+
+/// Registers the `MsgBuf` of the current process at `msgbuf`, 0 unregisters it.
+///
+/// The `MsgBuf` must take a whole page, so it can be written through one frame.
+pub fn sys_register_msgbuf(msgbuf: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    if msgbuf % PAGE_SIZE != 0 || core::mem::size_of::<MsgBuf>() != PAGE_SIZE {
+        return Err(KernelError::InvalidArgs);
+    }
+    if msgbuf != 0 {
+        let mut mm = process.mm.lock();
+        mm.check_user(VirtAddr::from(msgbuf), PAGE_SIZE, VMFlags::READ | VMFlags::WRITE)?;
+        mm.alloc_frame(VirtAddr::from(msgbuf))?;
+    }
+    process.msgbuf.store(msgbuf, Ordering::Release);
+    Ok(0)
+}
+
+/// Wakes the coroutine awaiting `msg` as the key in the process `pid`.
+pub fn sys_send_msg(pid: usize, msg: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let target = find_process(pid).ok_or(KernelError::Errno(Errno::ESRCH))?;
+    target.post(Message::new(process.pid.0, msg, &[]))?;
+    Ok(0)
+}
+
+/// Posts the bytes in the buffer to the mailbox of the process `pid`, which are at most
+/// `MSG_PAYLOAD_SIZE` bytes.
+pub fn sys_mail_write(pid: usize, buf_ptr: usize, buf_len: usize) -> KernelResult<usize> {
+    if buf_len > MSG_PAYLOAD_SIZE {
+        return Err(KernelError::InvalidArgs);
+    }
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let target = find_process(pid).ok_or(KernelError::Errno(Errno::ESRCH))?;
+    let mut data = [0; MSG_PAYLOAD_SIZE];
+    let buf = process.mm.lock().get_buf(VirtAddr::from(buf_ptr), buf_len)?;
+    for (byte, ptr) in data.iter_mut().zip(buf.into_iter()) {
+        *byte = unsafe { *ptr };
+    }
+    target.post(Message::new(process.pid.0, MAILBOX_KEY, &data[..buf_len]))?;
+    Ok(buf_len)
+}
+
+/// Reads a message from the mailbox of the current process, returns `EAGAIN` if it is empty.
+///
+/// The payload is truncated to `buf_len` bytes.
+pub fn sys_mail_read(buf_ptr: usize, buf_len: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let message = process.mailbox.lock().pop_front().ok_or(KernelError::Errno(Errno::EAGAIN))?;
+    let data = message.data();
+    let len = buf_len.min(data.len());
+    let buf = process.mm.lock().get_buf_mut(VirtAddr::from(buf_ptr), len)?;
+    for (ptr, byte) in buf.into_iter().zip(data[..len].iter()) {
+        unsafe { *ptr = *byte };
+    }
+    Ok(len)
+}
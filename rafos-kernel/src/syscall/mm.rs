@@ -0,0 +1,47 @@
+use mmrv::VirtAddr;
+
+use crate::{
+    mm::{do_brk, do_mmap, do_mprotect, do_munmap, MmapFlags, MmapProt},
+    task::current_process,
+    KernelError, KernelResult,
+};
+
+pub fn sys_brk(brk: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let mut mm = process.mm.lock();
+    do_brk(&mut mm, VirtAddr::from(brk))
+}
+
+pub fn sys_mmap(
+    start: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    off: usize,
+) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let prot = MmapProt::from_bits(prot).ok_or(KernelError::InvalidArgs)?;
+    let flags = MmapFlags::from_bits(flags).ok_or(KernelError::InvalidArgs)?;
+    let file = if flags.contains(MmapFlags::MAP_ANONYMOUS) {
+        None
+    } else {
+        Some(process.fd_table.lock().get(fd)?)
+    };
+    let mut mm = process.mm.lock();
+    do_mmap(&mut mm, VirtAddr::from(start), len, prot, flags, file, off)
+}
+
+pub fn sys_munmap(start: usize, len: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let mut mm = process.mm.lock();
+    do_munmap(&mut mm, VirtAddr::from(start), len)?;
+    Ok(0)
+}
+
+pub fn sys_mprotect(start: usize, len: usize, prot: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let prot = MmapProt::from_bits(prot).ok_or(KernelError::InvalidArgs)?;
+    let mut mm = process.mm.lock();
+    do_mprotect(&mut mm, VirtAddr::from(start), len, prot)
+}
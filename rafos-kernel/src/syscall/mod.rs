@@ -0,0 +1,255 @@
+/// This mod implements the kernel side of the syscalls defined in `rafos-syscall`.
+///
+/// The syscall id is passed by `a7`, and the arguments are passed by `a0..a5`.
+/// The return value is the result on success, or the negated `Errno` on failure.
+
+mod fs;
+mod ipc;
+mod mm;
+mod process;
+mod sync;
+
+use errno::Errno;
+use ::syscall::{SyscallId, SyscallTrait};
+
+use crate::{KernelError, KernelResult};
+
+const SYSCALL_DUP: usize = SyscallId::Dup as usize;
+const SYSCALL_LINKAT: usize = SyscallId::Linkat as usize;
+const SYSCALL_OPEN: usize = SyscallId::Open as usize;
+const SYSCALL_CLOSE: usize = SyscallId::Close as usize;
+const SYSCALL_PIPE: usize = SyscallId::Pipe as usize;
+const SYSCALL_LSEEK: usize = SyscallId::Lseek as usize;
+const SYSCALL_PREAD: usize = SyscallId::Pread as usize;
+const SYSCALL_PWRITE: usize = SyscallId::Pwrite as usize;
+const SYSCALL_FSTAT: usize = SyscallId::Fstat as usize;
+const SYSCALL_SYNC: usize = SyscallId::Sync as usize;
+const SYSCALL_FSYNC: usize = SyscallId::Fsync as usize;
+const SYSCALL_READ: usize = SyscallId::Read as usize;
+const SYSCALL_WRITE: usize = SyscallId::Write as usize;
+const SYSCALL_EXIT: usize = SyscallId::Exit as usize;
+const SYSCALL_YIELD: usize = SyscallId::Yield as usize;
+const SYSCALL_GET_TIME: usize = SyscallId::GetTime as usize;
+const SYSCALL_GET_PID: usize = SyscallId::GetPid as usize;
+const SYSCALL_FORK: usize = SyscallId::Fork as usize;
+const SYSCALL_EXEC: usize = SyscallId::Exec as usize;
+const SYSCALL_WAIT_PID: usize = SyscallId::WaitPid as usize;
+const SYSCALL_BRK: usize = SyscallId::Brk as usize;
+const SYSCALL_MUNMAP: usize = SyscallId::Munmap as usize;
+const SYSCALL_MMAP: usize = SyscallId::Mmap as usize;
+const SYSCALL_MPROTECT: usize = SyscallId::Mprotect as usize;
+const SYSCALL_MUTEX_CREATE: usize = SyscallId::MutexCreate as usize;
+const SYSCALL_MUTEX_LOCK: usize = SyscallId::MutexLock as usize;
+const SYSCALL_MUTEX_UNLOCK: usize = SyscallId::MutexUnlock as usize;
+const SYSCALL_CONDVAR_CREATE: usize = SyscallId::CondvarCreate as usize;
+const SYSCALL_CONDVAR_SIGNAL: usize = SyscallId::CondvarSignal as usize;
+const SYSCALL_CONDVAR_WAIT: usize = SyscallId::CondvarWait as usize;
+const SYSCALL_MAIL_READ: usize = SyscallId::MailRead as usize;
+const SYSCALL_MAIL_WRITE: usize = SyscallId::MailWrite as usize;
+const SYSCALL_SEND_MSG: usize = SyscallId::SendMsg as usize;
+const SYSCALL_REGISTER_MSGBUF: usize = SyscallId::RegisterMsgBuf as usize;
+const SYSCALL_EXECUTOR_STATS: usize = SyscallId::ExecutorStats as usize;
+
+/// The kernel implementation of [`SyscallTrait`].
+pub struct SyscallHandler;
+
+impl SyscallTrait for SyscallHandler {
+    fn sys_dup(&self, fd: usize) -> isize {
+        into_ret(fs::sys_dup(fd))
+    }
+
+    fn sys_linkat(&self, olddirfd: usize, oldpath_ptr: usize, newdirfd: usize, newpath_ptr: usize, flags: usize) -> isize {
+        into_ret(fs::sys_linkat(olddirfd, oldpath_ptr, newdirfd, newpath_ptr, flags))
+    }
+
+    fn sys_open(&self, path_ptr: usize, flag_bits: usize) -> isize {
+        into_ret(fs::sys_open(path_ptr, flag_bits))
+    }
+
+    fn sys_close(&self, fd: usize) -> isize {
+        into_ret(fs::sys_close(fd))
+    }
+
+    fn sys_pipe(&self, pipe_ptr: usize, flags: usize) -> isize {
+        into_ret(fs::sys_pipe(pipe_ptr, flags))
+    }
+
+    fn sys_read(&self, fd: usize, buf_ptr: usize, buf_len: usize) -> isize {
+        into_ret(fs::sys_read(fd, buf_ptr, buf_len))
+    }
+
+    fn sys_write(&self, fd: usize, buf_ptr: usize, buf_len: usize) -> isize {
+        into_ret(fs::sys_write(fd, buf_ptr, buf_len))
+    }
+
+    fn sys_lseek(&self, fd: usize, offset: usize, whence: usize) -> isize {
+        into_ret(fs::sys_lseek(fd, offset, whence))
+    }
+
+    fn sys_pread(&self, fd: usize, buf_ptr: usize, buf_len: usize, offset: usize) -> isize {
+        into_ret(fs::sys_pread(fd, buf_ptr, buf_len, offset))
+    }
+
+    fn sys_pwrite(&self, fd: usize, buf_ptr: usize, buf_len: usize, offset: usize) -> isize {
+        into_ret(fs::sys_pwrite(fd, buf_ptr, buf_len, offset))
+    }
+
+    fn sys_fstat(&self, fd: usize, stat_ptr: usize) -> isize {
+        into_ret(fs::sys_fstat(fd, stat_ptr))
+    }
+
+    fn sys_sync(&self) -> isize {
+        into_ret(fs::sys_sync())
+    }
+
+    fn sys_fsync(&self, fd: usize) -> isize {
+        into_ret(fs::sys_fsync(fd))
+    }
+
+    fn sys_exit(&self, exit_code: usize) -> isize {
+        into_ret(process::sys_exit(exit_code))
+    }
+
+    fn sys_yield(&self) -> isize {
+        into_ret(process::sys_yield())
+    }
+
+    fn sys_get_time(&self, time_ptr: usize, tz: usize) -> isize {
+        into_ret(process::sys_get_time(time_ptr, tz))
+    }
+
+    fn sys_get_pid(&self) -> isize {
+        into_ret(process::sys_get_pid())
+    }
+
+    fn sys_fork(&self) -> isize {
+        into_ret(process::sys_fork())
+    }
+
+    fn sys_exec(&self, path_ptr: usize, args_ptr: usize) -> isize {
+        into_ret(process::sys_exec(path_ptr, args_ptr))
+    }
+
+    fn sys_wait_pid(&self, pid: usize, exit_code_ptr: usize) -> isize {
+        into_ret(process::sys_wait_pid(pid, exit_code_ptr))
+    }
+
+    fn sys_brk(&self, brk: usize) -> isize {
+        into_ret(mm::sys_brk(brk))
+    }
+
+    fn sys_munmap(&self, start: usize, len: usize) -> isize {
+        into_ret(mm::sys_munmap(start, len))
+    }
+
+    fn sys_mmap(&self, start: usize, len: usize, prot: usize, flags: usize, fd: usize, off: usize) -> isize {
+        into_ret(mm::sys_mmap(start, len, prot, flags, fd, off))
+    }
+
+    fn sys_mprotect(&self, start: usize, len: usize, prot: usize) -> isize {
+        into_ret(mm::sys_mprotect(start, len, prot))
+    }
+
+    fn sys_mutex_create(&self, blocking: usize) -> isize {
+        into_ret(sync::sys_mutex_create(blocking))
+    }
+
+    fn sys_mutex_lock(&self, id: usize) -> isize {
+        into_ret(sync::sys_mutex_lock(id))
+    }
+
+    fn sys_mutex_unlock(&self, id: usize) -> isize {
+        into_ret(sync::sys_mutex_unlock(id))
+    }
+
+    fn sys_condvar_create(&self, arg: usize) -> isize {
+        into_ret(sync::sys_condvar_create(arg))
+    }
+
+    fn sys_condvar_signal(&self, condvar_id: usize) -> isize {
+        into_ret(sync::sys_condvar_signal(condvar_id))
+    }
+
+    fn sys_condvar_wait(&self, condvar_id: usize, mutex_id: usize) -> isize {
+        into_ret(sync::sys_condvar_wait(condvar_id, mutex_id))
+    }
+
+    fn sys_mail_read(&self, buf_ptr: usize, buf_len: usize) -> isize {
+        into_ret(ipc::sys_mail_read(buf_ptr, buf_len))
+    }
+
+    fn sys_mail_write(&self, pid: usize, buf_ptr: usize, buf_len: usize) -> isize {
+        into_ret(ipc::sys_mail_write(pid, buf_ptr, buf_len))
+    }
+
+    fn sys_send_msg(&self, pid: usize, msg: usize) -> isize {
+        into_ret(ipc::sys_send_msg(pid, msg))
+    }
+
+    fn sys_register_msg_buf(&self, msgbuf: usize) -> isize {
+        into_ret(ipc::sys_register_msgbuf(msgbuf))
+    }
+
+    fn sys_executor_stats(&self, hart: usize, stats_ptr: usize) -> isize {
+        into_ret(process::sys_executor_stats(hart, stats_ptr))
+    }
+}
+
+/// Dispatches the syscall to the [`SyscallHandler`].
+///
+/// # Argument
+/// - `id`: syscall id in `a7`.
+/// - `args`: arguments in `a0..a5`.
+pub fn syscall(id: usize, args: [usize; 6]) -> isize {
+    let handler = SyscallHandler;
+    log::trace!("syscall {} {:#X?}", id, args);
+    match id {
+        SYSCALL_DUP => handler.sys_dup(args[0]),
+        SYSCALL_LINKAT => handler.sys_linkat(args[0], args[1], args[2], args[3], args[4]),
+        SYSCALL_OPEN => handler.sys_open(args[0], args[1]),
+        SYSCALL_CLOSE => handler.sys_close(args[0]),
+        SYSCALL_PIPE => handler.sys_pipe(args[0], args[1]),
+        SYSCALL_READ => handler.sys_read(args[0], args[1], args[2]),
+        SYSCALL_WRITE => handler.sys_write(args[0], args[1], args[2]),
+        SYSCALL_LSEEK => handler.sys_lseek(args[0], args[1], args[2]),
+        SYSCALL_PREAD => handler.sys_pread(args[0], args[1], args[2], args[3]),
+        SYSCALL_PWRITE => handler.sys_pwrite(args[0], args[1], args[2], args[3]),
+        SYSCALL_FSTAT => handler.sys_fstat(args[0], args[1]),
+        SYSCALL_SYNC => handler.sys_sync(),
+        SYSCALL_FSYNC => handler.sys_fsync(args[0]),
+        SYSCALL_EXIT => handler.sys_exit(args[0]),
+        SYSCALL_YIELD => handler.sys_yield(),
+        SYSCALL_GET_TIME => handler.sys_get_time(args[0], args[1]),
+        SYSCALL_GET_PID => handler.sys_get_pid(),
+        SYSCALL_FORK => handler.sys_fork(),
+        SYSCALL_EXEC => handler.sys_exec(args[0], args[1]),
+        SYSCALL_WAIT_PID => handler.sys_wait_pid(args[0], args[1]),
+        SYSCALL_BRK => handler.sys_brk(args[0]),
+        SYSCALL_MUNMAP => handler.sys_munmap(args[0], args[1]),
+        SYSCALL_MMAP => handler.sys_mmap(args[0], args[1], args[2], args[3], args[4], args[5]),
+        SYSCALL_MPROTECT => handler.sys_mprotect(args[0], args[1], args[2]),
+        SYSCALL_MUTEX_CREATE => handler.sys_mutex_create(args[0]),
+        SYSCALL_MUTEX_LOCK => handler.sys_mutex_lock(args[0]),
+        SYSCALL_MUTEX_UNLOCK => handler.sys_mutex_unlock(args[0]),
+        SYSCALL_CONDVAR_CREATE => handler.sys_condvar_create(args[0]),
+        SYSCALL_CONDVAR_SIGNAL => handler.sys_condvar_signal(args[0]),
+        SYSCALL_CONDVAR_WAIT => handler.sys_condvar_wait(args[0], args[1]),
+        SYSCALL_MAIL_READ => handler.sys_mail_read(args[0], args[1]),
+        SYSCALL_MAIL_WRITE => handler.sys_mail_write(args[0], args[1], args[2]),
+        SYSCALL_SEND_MSG => handler.sys_send_msg(args[0], args[1]),
+        SYSCALL_REGISTER_MSGBUF => handler.sys_register_msg_buf(args[0]),
+        SYSCALL_EXECUTOR_STATS => handler.sys_executor_stats(args[0], args[1]),
+        _ => into_ret(Err(KernelError::SyscallUnsupported(id))),
+    }
+}
+
+/// Converts the result of a syscall into the value returned in `a0`.
+fn into_ret(result: KernelResult<usize>) -> isize {
+    match result {
+        Ok(ret) => ret as isize,
+        Err(err) => {
+            log::warn!("syscall failed: {:?}", err);
+            -(Errno::from(err) as isize)
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use core::sync::atomic::Ordering;
+
+use asyncc::Asyncc;
+use errno::Errno;
+use mmrv::VirtAddr;
+use time::Instant;
+
+use crate::{
+    fs::{open_file, OpenFlags},
+    task::{current_process, executor_stats, Image},
+    KernelError, KernelResult,
+};
+
+/// `struct timeval` in Linux.
+#[repr(C)]
+struct TimeVal {
+    sec: usize,
+    usec: usize,
+}
+
+/// Marks the current process as a zombie, and goes back to the kernel, since the address
+/// space is released. The process is released once the hart has left it.
+pub fn sys_exit(exit_code: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    process.exit(exit_code as i32);
+    drop(process);
+    unsafe { crate::trampoline::return_to_kernel() }
+}
+
+/// Creates a child process, returns the pid of it in the parent, and 0 in the child.
+///
+/// A coroutine can't be copied while it is being polled, so the copy of the calling coroutine
+/// polls again from its last `await` in the child, and its first `sys_fork` there returns 0.
+/// It is resumed through the `MsgBuf`, so it fails with `EINVAL` if there is no `MsgBuf`.
+pub fn sys_fork() -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let task = Asyncc::get_curr().ok_or(KernelError::InvalidArgs)?;
+    let task_ptr = task.as_ptr() as usize;
+    if process.forked.compare_exchange(task_ptr, 0, Ordering::AcqRel, Ordering::Relaxed).is_ok() {
+        return Ok(0);
+    }
+    let child = process.fork(task)?;
+    Ok(child.pid.0)
+}
+
+/// Loads the executable at `path_ptr` from easy-fs, which replaces the address space of the
+/// current process. The arguments are not supported yet.
+///
+/// It does not return on success: the coroutines of the old image are dropped with the old
+/// address space, and the process starts over from the new image.
+pub fn sys_exec(path_ptr: usize, _args_ptr: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    // The locals are not dropped after `return_to_kernel`.
+    let image = {
+        let path = process.mm.lock().get_str(VirtAddr::from(path_ptr))?;
+        let file = open_file(path.as_str(), OpenFlags::RDONLY).ok_or(KernelError::Errno(Errno::ENOENT))?;
+        Image::load(&file.read_all())?
+    };
+    process.exec(image);
+    drop(process);
+    unsafe { crate::trampoline::return_to_kernel() }
+}
+
+/// Reaps a zombie child, `pid` is -1 for any child. Writes the exit code to `exit_code_ptr`
+/// unless it is 0, and returns the pid of the child.
+///
+/// Returns `EAGAIN` if the child has not exited yet.
+pub fn sys_wait_pid(pid: usize, exit_code_ptr: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let (pid, exit_code) = process.wait(pid)?;
+    if exit_code_ptr != 0 {
+        process.mm.lock().alloc_write_type(VirtAddr::from(exit_code_ptr), &exit_code)?;
+    }
+    Ok(pid)
+}
+
+/// The user coroutines are scheduled by the user `Executor`, so there is nothing to do
+/// in the kernel.
+pub fn sys_yield() -> KernelResult<usize> {
+    Ok(0)
+}
+
+/// Writes the current time to `time_ptr`, the timezone is ignored.
+pub fn sys_get_time(time_ptr: usize, _tz: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let now = Instant::now().as_micros() as usize;
+    let time_val = TimeVal {
+        sec: now / 1_000_000,
+        usec: now % 1_000_000,
+    };
+    process.mm.lock().alloc_write_type(VirtAddr::from(time_ptr), &time_val)?;
+    Ok(0)
+}
+
+pub fn sys_get_pid() -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    Ok(process.pid.0)
+}
+
+/// Writes the statistics of the kernel `Executor` of `hart` to `stats_ptr`, `usize::MAX`
+/// selects the global one.
+pub fn sys_executor_stats(hart: usize, stats_ptr: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let stats = executor_stats(hart)?;
+    process.mm.lock().alloc_write_type(VirtAddr::from(stats_ptr), &stats)?;
+    Ok(0)
+}
@@ -0,0 +1,86 @@
+use errno::Errno;
+
+use crate::{
+    task::{current_process, current_task, UserCondvar, UserMutex},
+    KernelError, KernelResult,
+};
+
+// The syscalls are handled in the trap handler, which can't park itself, so the calling
+// coroutine is parked instead: the syscall returns `EAGAIN`, the coroutine returns `Pending`
+// and is resumed through the `MsgBuf` when the mutex is unlocked. Then it calls
+// `sys_mutex_lock` again, which returns 0 or parks it again.
+
+/// Creates a mutex in the current process, and returns its id.
+///
+/// All the mutexes park the waiters, so `blocking` is ignored.
+pub fn sys_mutex_create(_blocking: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let mut mutexes = process.mutexes.lock();
+    mutexes.push(UserMutex::default());
+    Ok(mutexes.len() - 1)
+}
+
+/// Locks the mutex, or parks the caller and returns `EAGAIN` if it is owned by another
+/// coroutine.
+pub fn sys_mutex_lock(id: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let (task, waker) = current_task(&process).ok_or(KernelError::InvalidArgs)?;
+    let mut mutexes = process.mutexes.lock();
+    let mutex = mutexes.get_mut(id).ok_or(KernelError::InvalidArgs)?;
+    if !mutex.lock(task, &waker) {
+        return Err(KernelError::Errno(Errno::EAGAIN));
+    }
+    Ok(0)
+}
+
+/// Unlocks the mutex, and resumes the first coroutine parked on it.
+///
+/// Returns `EPERM` if the caller does not own the mutex.
+pub fn sys_mutex_unlock(id: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let (task, _) = current_task(&process).ok_or(KernelError::InvalidArgs)?;
+    let mut mutexes = process.mutexes.lock();
+    let mutex = mutexes.get_mut(id).ok_or(KernelError::InvalidArgs)?;
+    mutex.unlock(task)?;
+    Ok(0)
+}
+
+/// Creates a condition variable in the current process, and returns its id.
+pub fn sys_condvar_create(_arg: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let mut condvars = process.condvars.lock();
+    condvars.push(UserCondvar::default());
+    Ok(condvars.len() - 1)
+}
+
+/// Signals the condition variable, and resumes the first waiter.
+///
+/// The signal is lost if there is no waiter.
+pub fn sys_condvar_signal(condvar_id: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let condvars = process.condvars.lock();
+    let condvar = condvars.get(condvar_id).ok_or(KernelError::InvalidArgs)?;
+    condvar.signal();
+    Ok(0)
+}
+
+/// Unlocks the mutex and parks the caller until the condition variable is signaled, always
+/// returns `EAGAIN`.
+///
+/// The caller is resumed when it is signaled, and must call `sys_mutex_lock` before checking
+/// the condition. Returns `EPERM` if the caller does not own the mutex.
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> KernelResult<usize> {
+    let process = current_process().ok_or(KernelError::InvalidArgs)?;
+    let (task, waker) = current_task(&process).ok_or(KernelError::InvalidArgs)?;
+    let condvars = process.condvars.lock();
+    let condvar = condvars.get(condvar_id).ok_or(KernelError::InvalidArgs)?;
+    let mut mutexes = process.mutexes.lock();
+    let mutex = mutexes.get_mut(mutex_id).ok_or(KernelError::InvalidArgs)?;
+    if !mutex.is_owned_by(task) {
+        return Err(KernelError::Errno(Errno::EPERM));
+    }
+    // Registers before unlocking, so a signal after the unlock is not lost.
+    condvar.wait(&waker);
+    mutex.unlock(task)?;
+    Err(KernelError::Errno(Errno::EAGAIN))
+}
@@ -0,0 +1,36 @@
+/// This mod records the `Process` running on each hart.
+///
+
+use alloc::sync::Arc;
+use config::CPU_NUM;
+use spin::Mutex;
+
+use super::Process;
+
+const NONE_PROCESS: Mutex<Option<Arc<Process>>> = Mutex::new(None);
+
+/// The `Process` which the hart is going to run, or is running.
+static CURRENT_PROCESS: [Mutex<Option<Arc<Process>>>; CPU_NUM] = [NONE_PROCESS; CPU_NUM];
+
+/// Returns the id of the current hart, which is saved in `tp` when booting.
+#[inline(always)]
+pub fn hart_id() -> usize {
+    let hart_id: usize;
+    unsafe { core::arch::asm!("mv {}, tp", out(reg) hart_id) };
+    hart_id
+}
+
+/// Returns the `Process` running on the current hart.
+pub fn current_process() -> Option<Arc<Process>> {
+    CURRENT_PROCESS[hart_id()].lock().clone()
+}
+
+/// Records the `Process` running on the current hart.
+pub fn set_current_process(process: Option<Arc<Process>>) {
+    *CURRENT_PROCESS[hart_id()].lock() = process;
+}
+
+/// Takes the `Process` which the current hart has left.
+pub fn take_current_process() -> Option<Arc<Process>> {
+    CURRENT_PROCESS[hart_id()].lock().take()
+}
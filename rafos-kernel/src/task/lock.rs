@@ -0,0 +1,128 @@
+/// This mod defines the mutexes and condition variables of user coroutines.
+///
+/// They are backed by the primitives of `sync`. A coroutine which has to wait is parked in the
+/// kernel with a [`resume_waker`], and its user `Executor` does not poll it until the waker
+/// posts a resume `Message` to the `MsgBuf` of the process.
+
+use alloc::{
+    sync::{Arc, Weak},
+    task::Wake,
+};
+use asyncc::{Asyncc, Message, TaskRef};
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::Ordering,
+    task::{Context, Poll, Waker},
+};
+use errno::Errno;
+use sync::{Mutex, WaitQueue};
+
+use super::Process;
+use crate::{KernelError, KernelResult};
+
+/// Resumes a parked user coroutine of the process.
+struct Resume {
+    process: Weak<Process>,
+    task: TaskRef,
+}
+
+impl Wake for Resume {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let Some(process) = self.process.upgrade() else {
+            return;
+        };
+        if let Err(err) = process.post(Message::resume(self.task)) {
+            log::warn!("failed to resume {:?} of process {}: {:?}", self.task, process.pid.0, err);
+        }
+    }
+}
+
+/// The waker which resumes `task` of `process`, it does nothing once the process is gone.
+fn resume_waker(process: &Arc<Process>, task: TaskRef) -> Waker {
+    Waker::from(Arc::new(Resume { process: Arc::downgrade(process), task }))
+}
+
+/// The user coroutine which is making the syscall and the waker which resumes it, if it can
+/// be resumed through the `MsgBuf` of `process`.
+pub fn current_task(process: &Arc<Process>) -> Option<(TaskRef, Waker)> {
+    if process.msgbuf.load(Ordering::Acquire) == 0 {
+        return None;
+    }
+    let task = Asyncc::get_curr()?;
+    Some((task, resume_waker(process, task)))
+}
+
+/// A mutex owned by the user coroutine which locked it.
+#[derive(Default)]
+pub struct UserMutex {
+    /// Locked while a coroutine owns it, the guard is forgotten in [`UserMutex::lock`].
+    mutex: Mutex<()>,
+    owner: Option<TaskRef>,
+}
+
+impl UserMutex {
+    /// Locks the mutex for `task`, or parks it with `waker` if the mutex is owned by another
+    /// one.
+    ///
+    /// Returns `true` if `task` owns the mutex. A parked coroutine is woken when the mutex is
+    /// unlocked, then it has to lock it again.
+    pub fn lock(&mut self, task: TaskRef, waker: &Waker) -> bool {
+        if self.owner == Some(task) {
+            return true;
+        }
+        let mut lock = self.mutex.lock();
+        match Pin::new(&mut lock).poll(&mut Context::from_waker(waker)) {
+            Poll::Ready(guard) => {
+                core::mem::forget(guard);
+                self.owner = Some(task);
+                true
+            }
+            Poll::Pending => {
+                // Dropping the future would pass the wakeup on to another waiter.
+                core::mem::forget(lock);
+                false
+            }
+        }
+    }
+
+    /// Unlocks the mutex owned by `task`, and wakes the first parked coroutine.
+    ///
+    /// Returns `EPERM` if `task` is not the owner.
+    pub fn unlock(&mut self, task: TaskRef) -> KernelResult {
+        if !self.is_owned_by(task) {
+            return Err(KernelError::Errno(Errno::EPERM));
+        }
+        self.owner = None;
+        // The guard was forgotten when `task` locked it.
+        unsafe { self.mutex.force_unlock() };
+        Ok(())
+    }
+
+    /// Whether `task` owns the mutex.
+    pub fn is_owned_by(&self, task: TaskRef) -> bool {
+        self.owner == Some(task)
+    }
+}
+
+/// A condition variable, whose waiters are parked until it is signaled.
+#[derive(Default)]
+pub struct UserCondvar {
+    waiters: WaitQueue,
+}
+
+impl UserCondvar {
+    /// Parks the coroutine of `waker` until it is signaled.
+    pub fn wait(&self, waker: &Waker) {
+        self.waiters.register(waker);
+    }
+
+    /// Wakes the first waiter, the signal is lost if there is none.
+    pub fn signal(&self) {
+        self.waiters.wake_one();
+    }
+}
@@ -5,8 +5,14 @@
 
 mod process;
 mod id;
+mod current;
+mod sched;
+mod lock;
 
 pub use process::*;
+pub use current::*;
+pub use sched::*;
+pub use lock::*;
 use id::*;
 
 
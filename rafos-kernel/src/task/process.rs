@@ -1,5 +1,5 @@
 use core::{
-    sync::atomic::AtomicI32, 
+    sync::atomic::{AtomicI32, AtomicUsize, Ordering},
     future::Future,
     pin::Pin,
     task::{Poll, Context},
@@ -8,130 +8,431 @@ use core::{
 use super::*;
 use asyncc::*;
 use buddy_system_allocator::LockedHeap;
-use config::{USER_HEAP_SIZE, USER_HEAP_PTR, PAGE_MASK};
+use config::{KERNEL_STACK_SIZE, USER_HEAP_SIZE, USER_HEAP_PTR, PAGE_MASK};
+use mmrv::{AllocatedFrameRange, VirtAddr, PAGE_SIZE};
 /// This mod define `Process`
 /// 
 
 use spin::{Lazy, Mutex};
-use alloc::{vec::Vec, sync::{Arc, Weak}, boxed::Box};
-use crate::{mm::{MM, VMFlags}, fs::{File, FDManager}, KernelError};
+use alloc::{vec::Vec, sync::{Arc, Weak}, boxed::Box, collections::{BTreeMap, VecDeque}};
+use crate::{mm::{kernel_activate, new_kernel, MM, VMFlags}, fs::{File, FDManager}, loader, KernelError, KernelResult};
+use errno::Errno;
 
 use super::TaskState;
 
 pub static IDLE_PROCESS: Lazy<Arc<Process>> = Lazy::new(|| Arc::new(Process::idle()));
 
+/// The user processes indexed by pid, so a process can be found by the others.
+static PROCESS_TABLE: Mutex<BTreeMap<usize, Weak<Process>>> = Mutex::new(BTreeMap::new());
+
+/// The frames of the `Executor`s of the dropped kernel processes, which are kept until the
+/// tasks in them are freed, see [`reap_executors`].
+static ORPHAN_EXECUTORS: Mutex<Vec<AllocatedFrameRange>> = Mutex::new(Vec::new());
+
+/// Drops the ready tasks in the `Executor`s of the dropped kernel processes, and frees the
+/// ones which have no task left.
+///
+/// The pending tasks may be woken later, e.g. by a timer, so they are checked again when
+/// another process finishes.
+fn reap_executors() {
+    // A dropped future may drop another kernel process, which pushes its frames.
+    let orphans = core::mem::take(&mut *ORPHAN_EXECUTORS.lock());
+    let mut alive = Vec::new();
+    for frames in orphans {
+        let executor = frames.start.start_address().value() as *mut Executor;
+        let executor_ref = unsafe { &*executor };
+        executor_ref.drain();
+        if executor_ref.task_count() == 0 {
+            unsafe { core::ptr::drop_in_place(executor) };
+        } else {
+            alive.push(frames);
+        }
+    }
+    ORPHAN_EXECUTORS.lock().extend(alive);
+}
+
+/// Finds the user process of `pid`.
+pub fn find_process(pid: usize) -> Option<Arc<Process>> {
+    PROCESS_TABLE.lock().get(&pid).and_then(Weak::upgrade)
+}
+
 
 pub struct Process {
     // immutable
     pub pid: PidHandle,
     // mutable
-    pub executor: Option<usize>,
-    pub allocator: Option<&'static LockedHeap<32>>,
+    /// The user address of the `Executor`, 0 for the idle process.
+    pub executor: AtomicUsize,
+    /// The user address of the heap allocator, 0 for the idle process.
+    pub allocator: AtomicUsize,
     pub state: Mutex<TaskState>,
     pub mm: Mutex<MM>,
     pub parent: Mutex<Option<Weak<Process>>>,
     pub children: Mutex<Vec<Arc<Process>>>,
     pub exit_code: AtomicI32,
     pub fd_table: Mutex<FDManager>,
+    /// The mutexes created by `sys_mutex_create`, indexed by id.
+    pub mutexes: Mutex<Vec<UserMutex>>,
+    /// The condition variables created by `sys_condvar_create`, indexed by id.
+    pub condvars: Mutex<Vec<UserCondvar>>,
+    /// The user address of the registered `MsgBuf`, 0 if there is none.
+    pub msgbuf: AtomicUsize,
+    /// The messages posted to a process without a `MsgBuf`, read by `sys_mail_read`.
+    pub mailbox: Mutex<VecDeque<Message>>,
+    /// The `ProcessTask` of the process, which is woken or parked by [`Process::leave`].
+    task: Mutex<Option<TaskRef>>,
+    /// The pointer of the `TaskRef` which returns 0 from `sys_fork` in a forked child, 0 once
+    /// it has returned.
+    pub forked: AtomicUsize,
+    /// The stack which the trampoline switches to when entering the process, which is
+    /// allocated once and freed on exit.
+    pub stack: Mutex<Option<AllocatedFrameRange>>,
+    /// The frames of the `Executor` of a kernel process.
+    pub executor_frames: Option<AllocatedFrameRange>,
+}
+
+/// Allocates the stack of a process.
+fn alloc_stack() -> KernelResult<AllocatedFrameRange> {
+    AllocatedFrameRange::new(KERNEL_STACK_SIZE / PAGE_SIZE, false).map_err(|_| KernelError::FrameAllocFailed)
+}
+
+/// The address space of a user process loaded from an ELF executable.
+pub struct Image {
+    pub mm: MM,
+    pub executor: usize,
+    pub allocator: usize,
+}
+
+impl Image {
+    /// Loads the ELF executable into a new address space, which is organized as:
+    /// - `PT_LOAD` segments of the executable, followed by `start_brk`.
+    /// - The `Executor` in a free area found below the heap.
+    /// - The heap area `[USER_HEAP_PTR - USER_HEAP_SIZE, USER_HEAP_PTR)`, which is mapped on
+    ///   demand.
+    /// - The heap allocator at `USER_HEAP_PTR`, which is initialized by the user runtime,
+    ///   since the free lists of the allocator are stored in user pages.
+    pub fn load(elf_data: &[u8]) -> KernelResult<Self> {
+        let mut mm = MM::new()?;
+        loader::from_elf(elf_data, &mut mm)?;
+
+        let heap_end = VirtAddr::from(USER_HEAP_PTR);
+        let heap_start = heap_end - USER_HEAP_SIZE;
+        mm.alloc_vma(heap_start, heap_end, VMFlags::READ | VMFlags::WRITE | VMFlags::USER, false, None)?;
+        mm.alloc_write_vma(None, heap_end, heap_end + PAGE_SIZE, VMFlags::READ | VMFlags::WRITE | VMFlags::USER)?;
+        mm.alloc_write_type(heap_end, &LockedHeap::<32>::new())?;
+
+        let executor_size = (core::mem::size_of::<Executor>() + PAGE_SIZE - 1) & PAGE_MASK;
+        let executor = mm.find_free_area(mm.start_brk, executor_size)?;
+        mm.alloc_write_vma(None, executor, executor + executor_size, VMFlags::READ | VMFlags::WRITE | VMFlags::USER)?;
+        mm.alloc_write_type(executor, &Executor::new())?;
+        log::debug!("{:?}", mm);
+        Ok(Self { mm, executor: executor.value(), allocator: heap_end.value() })
+    }
 }
 
 impl Process {
     pub fn idle() -> Self {
         Self {
             pid: PidHandle(IDLE_PID),
-            executor: None,
-            allocator: None,
+            executor: AtomicUsize::new(0),
+            allocator: AtomicUsize::new(0),
             state: Mutex::new(TaskState::RUNNABLE),
             mm: Mutex::new(MM::new().unwrap()),
             parent: Mutex::new(None),
             children: Mutex::new(Vec::new()),
             exit_code: AtomicI32::new(0),
-            fd_table: Mutex::new(FDManager::new())
+            fd_table: Mutex::new(FDManager::new()),
+            mutexes: Mutex::new(Vec::new()),
+            condvars: Mutex::new(Vec::new()),
+            msgbuf: AtomicUsize::new(0),
+            mailbox: Mutex::new(VecDeque::new()),
+            task: Mutex::new(None),
+            forked: AtomicUsize::new(0),
+            stack: Mutex::new(None),
+            executor_frames: None,
         }
     }
 
-    // pub fn new_kp() -> Result<TaskRef, KernelError> {
-    //     let mut mm = crate::mm::new_kernel()?;
-    //     let executor_size = core::mem::size_of::<Executor>();
-    //     let start_va = mm.find_free_area(mm.start_brk, (executor_size + PAGE_SIZE - 1) & PAGE_MASK)?;
-    //     let executor = mm.alloc_write_type(
-    //         start_va, 
-    //         VMFlags::READ | VMFlags::WRITE, 
-    //         &Executor::new()
-    //     )?;
-    //     log::debug!("{:?}", mm);
-    //     // TODO: initializing the main task and add to Executor
-    //     // executor.spawn(fut, priority, task_type);
-    //     let process = Self {
-    //         pid: pid_alloc(),
-    //         executor: Some(executor as *mut Executor as *mut usize as usize),
-    //         allocator: None,
-    //         state: Mutex::new(TaskState::RUNNABLE),
-    //         mm: Mutex::new(mm),
-    //         parent: Mutex::new(Some(Arc::downgrade(&IDLE_PROCESS))),
-    //         children: Mutex::new(Vec::new()),
-    //         exit_code: AtomicI32::new(0),
-    //         fd_table: Mutex::new(FDManager::new())
-    //     };
-    //     let task_ref = Asyncc::spawn(Box::new(process), 0, TaskType::KernelProcess);
-    //     Ok(task_ref)
-    // }
-    
-    
-    // // pub fn new() -> Result<TaskRef, KernelError> {
-    // pub fn new(elf_data: &[u8]) -> Result<TaskRef, KernelError> {
-    //     let mut mm = MM::new(false)?;
-    //     loader::from_elf(elf_data, &mut mm)?;
-    //     let heap_start = mm.start_brk + 0x80814000;
-    //     let heap_end = heap_start + USER_HEAP_SIZE;
-
-    //     mm.alloc_write_vma(None, heap_start, heap_end, VMFlags::READ | VMFlags::WRITE | VMFlags::USER)?;
-    //     let allocator = LockedHeap::<32>::new();
-    //     unsafe { allocator.lock().init(heap_start.0, USER_HEAP_SIZE) };
-    //     let allocator = mm.alloc_write_type::<LockedHeap<32>>(
-    //         USER_HEAP_PTR.into(), 
-    //         VMFlags::READ | VMFlags::WRITE | VMFlags::USER, 
-    //         &allocator
-    //     )?;
-    //     let executor_size = core::mem::size_of::<Executor>();
-    //     let start_va = mm.find_free_area(mm.start_brk, (executor_size + PAGE_SIZE - 1) & PAGE_MASK)?;
-    //     let executor = mm.alloc_write_type(
-    //         start_va, 
-    //         VMFlags::READ | VMFlags::WRITE | VMFlags::USER, 
-    //         &Executor::new()
-    //     )?;
-    //     log::debug!("{:?}", mm);
-    //     // TODO: initializing the main task and add to Executor
-    //     // executor.spawn(fut, priority, task_type);
-    //     let process = Self {
-    //         pid: pid_alloc(),
-    //         executor: Some(executor as *mut Executor as *mut usize as usize),
-    //         allocator: Some(allocator),
-    //         state: Mutex::new(TaskState::RUNNABLE),
-    //         mm: Mutex::new(mm),
-    //         parent: Mutex::new(Some(Arc::downgrade(&IDLE_PROCESS))),
-    //         children: Mutex::new(Vec::new()),
-    //         exit_code: AtomicI32::new(0),
-    //         fd_table: Mutex::new(FDManager::new())
-    //     };
-    //     let task_ref = Asyncc::spawn(Box::new(process), 0, TaskType::Process);
-    //     Ok(task_ref)
-    // }
+    /// Creates a kernel process whose main coroutine is `fut`, and spawns it in the global
+    /// `Executor`. The process exits with the output of `fut`.
+    ///
+    /// The address space is built by `new_kernel`, which maps the physical memory identically,
+    /// so the `Executor` in the frames of the process can be reached from the boot `Executor`,
+    /// which spawns the main coroutine into it.
+    pub fn new_kp(
+        fut: Box<dyn Future<Output = i32> + 'static + Send + Sync>,
+        priority: u32,
+    ) -> KernelResult<JoinHandle> {
+        let mm = new_kernel()?;
+        let frames = AllocatedFrameRange::new((core::mem::size_of::<Executor>() + PAGE_SIZE - 1) / PAGE_SIZE, false)
+            .map_err(|_| KernelError::FrameAllocFailed)?;
+        let executor_ptr = frames.start.start_address().value() as *mut Executor;
+        let executor: &'static Executor = unsafe {
+            executor_ptr.write(Executor::new());
+            &*executor_ptr
+        };
+        let process = Arc::new(Self {
+            pid: pid_alloc(),
+            executor: AtomicUsize::new(executor_ptr as usize),
+            allocator: AtomicUsize::new(0),
+            state: Mutex::new(TaskState::RUNNABLE),
+            mm: Mutex::new(mm),
+            parent: Mutex::new(Some(Arc::downgrade(&IDLE_PROCESS))),
+            children: Mutex::new(Vec::new()),
+            exit_code: AtomicI32::new(0),
+            fd_table: Mutex::new(FDManager::new()),
+            mutexes: Mutex::new(Vec::new()),
+            condvars: Mutex::new(Vec::new()),
+            msgbuf: AtomicUsize::new(0),
+            mailbox: Mutex::new(VecDeque::new()),
+            task: Mutex::new(None),
+            forked: AtomicUsize::new(0),
+            stack: Mutex::new(Some(alloc_stack()?)),
+            executor_frames: Some(frames),
+        });
+        let weak = Arc::downgrade(&process);
+        let main = async move {
+            let exit_code = fut.await;
+            if let Some(process) = weak.upgrade() {
+                process.exit(exit_code);
+            }
+            exit_code
+        };
+        spawn_in(executor, Box::new(main), priority, TaskType::Other)?;
+        PROCESS_TABLE.lock().insert(process.pid.0, Arc::downgrade(&process));
+        process.spawn_task(TaskType::KernelProcess)
+    }
+
+    /// Creates a user process from the ELF executable, and spawns it in the global `Executor`,
+    /// so that it can run on any hart.
+    ///
+    /// See [`Image::load`] for the layout of the user address space.
+    pub fn new(elf_data: &[u8]) -> Result<JoinHandle, KernelError> {
+        let image = Image::load(elf_data)?;
+        let process = Arc::new(Self {
+            pid: pid_alloc(),
+            executor: AtomicUsize::new(image.executor),
+            allocator: AtomicUsize::new(image.allocator),
+            state: Mutex::new(TaskState::RUNNABLE),
+            mm: Mutex::new(image.mm),
+            parent: Mutex::new(Some(Arc::downgrade(&IDLE_PROCESS))),
+            children: Mutex::new(Vec::new()),
+            exit_code: AtomicI32::new(0),
+            fd_table: Mutex::new(FDManager::new()),
+            mutexes: Mutex::new(Vec::new()),
+            condvars: Mutex::new(Vec::new()),
+            msgbuf: AtomicUsize::new(0),
+            mailbox: Mutex::new(VecDeque::new()),
+            task: Mutex::new(None),
+            forked: AtomicUsize::new(0),
+            stack: Mutex::new(Some(alloc_stack()?)),
+            executor_frames: None,
+        });
+        PROCESS_TABLE.lock().insert(process.pid.0, Arc::downgrade(&process));
+        process.spawn_task(TaskType::Process)
+    }
+
+    /// Creates a child which has a copy-on-write copy of the address space, and the same
+    /// opened files, then spawns it in the global `Executor`.
+    ///
+    /// The copy of the task `resume`, which is being polled, is put back to the run queue of
+    /// the child through its `MsgBuf`, so it polls again and returns from `sys_fork`. Returns
+    /// `InvalidArgs` if there is no `MsgBuf`.
+    ///
+    /// The mutexes and condition variables are not shared, the child gets unlocked ones with
+    /// the same ids. The mailbox starts empty.
+    pub fn fork(self: &Arc<Self>, resume: TaskRef) -> KernelResult<Arc<Process>> {
+        if self.msgbuf.load(Ordering::Acquire) == 0 {
+            return Err(KernelError::InvalidArgs);
+        }
+        let mm = self.mm.lock().clone()?;
+        let mutexes = (0..self.mutexes.lock().len()).map(|_| UserMutex::default()).collect();
+        let condvars = (0..self.condvars.lock().len()).map(|_| UserCondvar::default()).collect();
+        let child = Arc::new(Self {
+            pid: pid_alloc(),
+            executor: AtomicUsize::new(self.executor.load(Ordering::Relaxed)),
+            allocator: AtomicUsize::new(self.allocator.load(Ordering::Relaxed)),
+            state: Mutex::new(TaskState::RUNNABLE),
+            mm: Mutex::new(mm),
+            parent: Mutex::new(Some(Arc::downgrade(self))),
+            children: Mutex::new(Vec::new()),
+            exit_code: AtomicI32::new(0),
+            fd_table: Mutex::new(self.fd_table.lock().clone()),
+            mutexes: Mutex::new(mutexes),
+            condvars: Mutex::new(condvars),
+            msgbuf: AtomicUsize::new(self.msgbuf.load(Ordering::Acquire)),
+            mailbox: Mutex::new(VecDeque::new()),
+            task: Mutex::new(None),
+            forked: AtomicUsize::new(resume.as_ptr() as usize),
+            stack: Mutex::new(Some(alloc_stack()?)),
+            executor_frames: None,
+        });
+        child.post(Message::resume(resume))?;
+        PROCESS_TABLE.lock().insert(child.pid.0, Arc::downgrade(&child));
+        child.spawn_task(TaskType::Process)?;
+        self.children.lock().push(child.clone());
+        Ok(child)
+    }
+
+    /// Spawns the `ProcessTask` of the process in the global `Executor`, and records it.
+    fn spawn_task(self: &Arc<Self>, task_type: TaskType) -> KernelResult<JoinHandle> {
+        // Held until the task is recorded, since the process may be entered and leave at once
+        // on another hart.
+        let mut task = self.task.lock();
+        let handle = spawn_global(Box::new(ProcessTask(self.clone())), 0, task_type)?;
+        *task = Some(handle.task_ref());
+        Ok(handle)
+    }
+
+    /// Whether the process has exited.
+    pub fn is_zombie(&self) -> bool {
+        self.state.lock().contains(TaskState::ZOMBIE)
+    }
+
+    /// The `Executor` of a kernel process, `None` for a user process.
+    pub fn kernel_executor(&self) -> Option<&'static Executor> {
+        self.executor_frames.as_ref()?;
+        Some(unsafe { &*(self.executor.load(Ordering::Relaxed) as *const Executor) })
+    }
+
+    /// Hands the process back to its `ProcessTask` after the hart has left it, which is called
+    /// on the boot stack, so the stack of the process is not in use any more.
+    ///
+    /// - An exited process is released by its `ProcessTask`.
+    /// - A kernel process is entered again once a task in its `Executor` is ready.
+    /// - A user process is entered again at once, e.g. after `sys_exec`.
+    pub fn leave(&self) {
+        let mut task = self.task.lock();
+        let Some(task_ref) = *task else {
+            return;
+        };
+        if self.is_zombie() {
+            // The task finishes when it is polled, so it must not be used again.
+            *task = None;
+            wake_task(task_ref);
+        } else if let Some(executor) = self.kernel_executor() {
+            // The task is pending until it is woken, so it is not freed.
+            executor.register_waker(&unsafe { from_task(task_ref) });
+        } else {
+            wake_task(task_ref);
+        }
+    }
+
+    /// Replaces the address space with the image, the process starts over from it when it is
+    /// entered next time.
+    ///
+    /// The old address space is released at once, so the caller must not return to the
+    /// process, and the hart switches to the kernel address space here. The mutexes and
+    /// condition variables of the old image are dropped.
+    pub fn exec(&self, image: Image) {
+        kernel_activate();
+        *self.mm.lock() = image.mm;
+        self.executor.store(image.executor, Ordering::Relaxed);
+        self.allocator.store(image.allocator, Ordering::Relaxed);
+        self.msgbuf.store(0, Ordering::Release);
+        self.forked.store(0, Ordering::Relaxed);
+        self.mutexes.lock().clear();
+        self.condvars.lock().clear();
+    }
+
+    /// Marks the process as a zombie, whose exit code is kept until the parent reaps it.
+    ///
+    /// The children are handed to [`IDLE_PROCESS`], which never waits, so they are reaped as
+    /// soon as they exit. The address space and the opened files are released at once, so
+    /// the caller must not return to the process, and the hart switches to the kernel
+    /// address space here. The stack and the pid are released by the `ProcessTask` after the
+    /// hart has left the process, see [`Process::leave`].
+    pub fn exit(&self, exit_code: i32) {
+        self.exit_code.store(exit_code, Ordering::Relaxed);
+        *self.state.lock() = TaskState::ZOMBIE;
+        for child in core::mem::take(&mut *self.children.lock()) {
+            *child.parent.lock() = Some(Arc::downgrade(&IDLE_PROCESS));
+        }
+        kernel_activate();
+        self.mm.lock().clear();
+        self.fd_table.lock().clear();
+        // Its writes must not wait for the flusher, the machine may be powered off next.
+        crate::fs::sync_all();
+    }
+
+    /// Reaps a zombie child, and returns its pid and exit code.
+    ///
+    /// `pid` selects the child, or any child if it is `usize::MAX`. Returns `ECHILD` if there is
+    /// no such child, or `EAGAIN` if it has not exited yet.
+    pub fn wait(&self, pid: usize) -> KernelResult<(usize, i32)> {
+        let mut children = self.children.lock();
+        if !children.iter().any(|child| pid == usize::MAX || child.pid.0 == pid) {
+            return Err(KernelError::Errno(Errno::ECHILD));
+        }
+        let index = children
+            .iter()
+            .position(|child| (pid == usize::MAX || child.pid.0 == pid) && child.is_zombie())
+            .ok_or(KernelError::Errno(Errno::EAGAIN))?;
+        // The pid is freed when the `ProcessTask` of the child finishes too.
+        let child = children.remove(index);
+        Ok((child.pid.0, child.exit_code.load(Ordering::Relaxed)))
+    }
 }
 
-impl Future for Process {
+impl Process {
+    /// Posts the message to this process, returns `EAGAIN` if the queue is full, or the
+    /// `MsgBuf` is too contended to enqueue.
+    ///
+    /// The message is enqueued into the registered `MsgBuf` through its frame, so the user
+    /// `Executor` dispatches it without switching to the kernel. Otherwise it is kept in the
+    /// mailbox.
+    pub fn post(&self, message: Message) -> KernelResult {
+        let msgbuf = self.msgbuf.load(Ordering::Acquire);
+        if msgbuf != 0 {
+            let frame = self.mm.lock().alloc_frame(VirtAddr::from(msgbuf))?;
+            let queue = unsafe { &*(frame.as_slice_mut().as_ptr() as *const MsgQueue) };
+            return queue.enqueue(message).map_err(|_| KernelError::Errno(Errno::EAGAIN));
+        }
+        let mut mailbox = self.mailbox.lock();
+        if mailbox.len() >= MSGQUEUE_SIZE {
+            return Err(KernelError::Errno(Errno::EAGAIN));
+        }
+        mailbox.push_back(message);
+        Ok(())
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        PROCESS_TABLE.lock().remove(&self.pid.0);
+        if let Some(frames) = self.executor_frames.take() {
+            ORPHAN_EXECUTORS.lock().push(frames);
+            reap_executors();
+        }
+    }
+}
+
+/// The coroutine of a user [`Process`] in the kernel `Executor`.
+///
+/// The `Process` is shared with the hart which is running it, so the syscall
+/// handlers can find its address space and file descriptors.
+pub struct ProcessTask(pub Arc<Process>);
+
+impl Future for ProcessTask {
     type Output = i32;
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        if self.state.lock().contains(TaskState::ZOMBIE) {
-            Poll::Ready(self.exit_code.load(core::sync::atomic::Ordering::Relaxed))
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let process = &self.0;
+        if process.is_zombie() {
+            // Woken by `Process::leave`, so the stack is not in use. The process is dropped
+            // with this task unless the parent has not reaped it.
+            process.stack.lock().take();
+            reap_executors();
+            Poll::Ready(process.exit_code.load(Ordering::Relaxed))
         } else {
-            let token = self.mm.lock().page_table.satp();
-            let executor = self.executor.unwrap();
-            Asyncc::set_args2(token, executor);
-            log::debug!("into process token: {:#X}, executor: {:#X}", token, executor);
+            let token = process.mm.lock().page_table.satp();
+            let executor = process.executor.load(Ordering::Relaxed);
+            let stack = process.stack.lock().as_ref().map_or(0, |stack| stack.end.start_address().value());
+            let msgbuf = process.msgbuf.load(Ordering::Acquire);
+            set_current_process(Some(process.clone()));
+            Asyncc::set_args4(token, executor, stack, msgbuf);
+            log::debug!("into process token: {:#X}, executor: {:#X}, stack: {:#X}", token, executor, stack);
             Poll::Pending
         }
     }
 }
-
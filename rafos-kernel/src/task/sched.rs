@@ -0,0 +1,93 @@
+/// This mod holds the kernel `Executor`s.
+///
+/// Each hart has its own `Executor`, whose tasks run on that hart unless an idle hart steals
+/// them. The tasks spawned in [`GLOBAL_EXECUTOR`] can be fetched by any hart, so they run in
+/// parallel.
+///
+/// The registers of Asyncc controller are banked per hart, so every hart resets it to its
+/// own `Executor` when booting.
+
+use alloc::boxed::Box;
+use asyncc::{Executor, ExecutorStats, JoinHandle, SpawnError, TaskRef, TaskType};
+use config::CPU_NUM;
+use core::future::Future;
+use errno::Errno;
+
+use super::hart_id;
+use crate::{KernelError, KernelResult};
+
+const EMPTY_EXECUTOR: Executor = Executor::new();
+
+/// The `Executor` of each hart.
+static EXECUTORS: [Executor; CPU_NUM] = [EMPTY_EXECUTOR; CPU_NUM];
+
+/// The run queues shared by all harts.
+static GLOBAL_EXECUTOR: Executor = Executor::new();
+
+/// Returns the `Executor` of the current hart.
+pub fn local_executor() -> &'static Executor {
+    &EXECUTORS[hart_id()]
+}
+
+/// The `hart` which selects [`GLOBAL_EXECUTOR`] in [`executor_stats`].
+pub const GLOBAL_HART: usize = usize::MAX;
+
+/// Returns the statistics of the `Executor` of `hart`, or of the global one if `hart` is
+/// [`GLOBAL_HART`].
+pub fn executor_stats(hart: usize) -> KernelResult<ExecutorStats> {
+    match hart {
+        GLOBAL_HART => Ok(GLOBAL_EXECUTOR.stats()),
+        _ => EXECUTORS.get(hart).map(Executor::stats).ok_or(KernelError::InvalidArgs),
+    }
+}
+
+/// Spawns a task which can run on any hart.
+///
+/// Fails with `EAGAIN` if there are too many ready tasks.
+pub fn spawn_global(
+    fut: Box<dyn Future<Output = i32> + 'static + Send + Sync>,
+    priority: u32,
+    task_type: TaskType,
+) -> KernelResult<JoinHandle> {
+    spawn_in(&GLOBAL_EXECUTOR, fut, priority, task_type)
+}
+
+/// Spawns a task in the `Executor`, e.g. the one of a kernel process.
+///
+/// Fails with `EAGAIN` if there are too many ready tasks.
+pub fn spawn_in(
+    executor: &'static Executor,
+    fut: Box<dyn Future<Output = i32> + 'static + Send + Sync>,
+    priority: u32,
+    task_type: TaskType,
+) -> KernelResult<JoinHandle> {
+    executor.try_spawn(fut, priority, task_type).map_err(|err| match err {
+        SpawnError::Full => KernelError::Errno(Errno::EAGAIN),
+        SpawnError::InvalidPriority => KernelError::InvalidArgs,
+    })
+}
+
+/// Fetches the next task to run on the current hart.
+///
+/// The local and global `Executor` are compared by their ready bitmaps, so the task which has
+/// the higher priority goes first, and the local one wins a tie. If both are empty, the hart
+/// steals from the busiest hart. If there is still nothing to run, the hart sleeps until an
+/// interrupt and tries again.
+pub fn fetch_task() -> TaskRef {
+    let local = local_executor();
+    loop {
+        let local_first = match (local.highest_ready_priority(), GLOBAL_EXECUTOR.highest_ready_priority()) {
+            (Some(local_prio), Some(global_prio)) => local_prio <= global_prio,
+            (_, global_prio) => global_prio.is_none(),
+        };
+        let task_ref = if local_first {
+            local.fetch().or_else(|| GLOBAL_EXECUTOR.fetch())
+        } else {
+            GLOBAL_EXECUTOR.fetch().or_else(|| local.fetch())
+        };
+        if let Some(task_ref) = task_ref.or_else(|| local.steal_from_busiest(EXECUTORS.iter())) {
+            return task_ref;
+        }
+        crate::timer::wait_for_interrupt();
+    }
+}
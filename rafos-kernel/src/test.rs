@@ -0,0 +1,44 @@
+/// This mod holds the tests which run in the kernel, enabled by the `kernel-test` feature.
+///
+/// They are spawned in place of the shell, a failed test panics, which powers off the machine
+/// with a failure.
+
+use alloc::{boxed::Box, sync::Arc};
+use sbi_rt::{system_reset, NoReason, Shutdown};
+
+use crate::task::{current_process, find_process, Process};
+
+/// Runs the tests one by one, and powers off the machine when all of them pass.
+pub async fn run() -> i32 {
+    exit_test().await;
+    log::info!("kernel tests passed");
+    system_reset(Shutdown, NoReason);
+    0
+}
+
+/// Exits a kernel process, and checks that its stack is freed before it is reaped, and its
+/// pid is freed after.
+async fn exit_test() {
+    let process = exit_kp(7).await;
+    assert!(process.is_zombie() && process.stack.lock().is_none());
+    let pid = process.pid.0;
+    drop(process);
+    assert!(find_process(pid).is_none());
+    // The pid is reused by the next process.
+    assert_eq!(exit_kp(0).await.pid.0, pid);
+}
+
+/// Runs a kernel process which exits with `exit_code` at once, and returns it like a parent
+/// which has not reaped it yet.
+async fn exit_kp(exit_code: i32) -> Arc<Process> {
+    let (tx, rx) = sync::oneshot::channel();
+    let main = async move {
+        let process = current_process().unwrap();
+        assert!(process.stack.lock().is_some());
+        let _ = tx.send(process);
+        exit_code
+    };
+    let handle = Process::new_kp(Box::new(main), 0).unwrap();
+    assert_eq!(handle.await, Some(exit_code));
+    rx.await.unwrap()
+}
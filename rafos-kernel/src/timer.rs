@@ -1,6 +1,19 @@
+use core::sync::atomic::{AtomicU64, Ordering};
 
+use config::CPU_NUM;
 use time::driver::Driver;
-use time::time_driver_impl;
+use time::{time_driver_impl, Duration};
+
+use crate::task::hart_id;
+
+/// The longest time an idle hart sleeps, since the tasks woken by other harts do not
+/// interrupt it.
+const IDLE_TIMEOUT: Duration = Duration::from_millis(1);
+
+const NO_ALARM: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// The alarm armed on each hart by the time driver.
+static ALARMS: [AtomicU64; CPU_NUM] = [NO_ALARM; CPU_NUM];
 
 struct TimeDriver;
 
@@ -8,8 +21,39 @@ impl Driver for TimeDriver {
     fn now(&self) -> u64 {
         riscv::register::time::read64()
     }
+
+    /// The alarm is armed on the current hart, and any hart handles the expired timers.
+    fn set_alarm(&self, timestamp: u64) {
+        ALARMS[hart_id()].store(timestamp, Ordering::Relaxed);
+        sbi_rt::set_timer(timestamp);
+    }
 }
 
 time_driver_impl!(static TIME_DRIVER: TimeDriver = TimeDriver);
 
+/// Enables the timer interrupt of the current hart.
+pub fn init() {
+    unsafe { riscv::register::sie::set_stimer() };
+}
+
+/// Handles the timer interrupt, which wakes the tasks of expired timers.
+pub fn handle_timer_interrupt() {
+    // Clears the pending interrupt, `on_alarm` arms the alarm again if there are timers left.
+    ALARMS[hart_id()].store(u64::MAX, Ordering::Relaxed);
+    sbi_rt::set_timer(u64::MAX);
+    time::on_alarm();
+}
 
+/// Sleeps until an interrupt arrives or [`IDLE_TIMEOUT`] passes.
+///
+/// The interrupts enabled in `sie` are taken while sleeping, so the timer and the devices can
+/// wake tasks.
+pub fn wait_for_interrupt() {
+    let timeout = TIME_DRIVER.now() + IDLE_TIMEOUT.as_ticks();
+    sbi_rt::set_timer(timeout.min(ALARMS[hart_id()].load(Ordering::Relaxed)));
+    unsafe {
+        riscv::register::sstatus::set_sie();
+        riscv::asm::wfi();
+        riscv::register::sstatus::clear_sie();
+    }
+}
@@ -1,15 +1,15 @@
-use core::{task::{Context, Poll}, sync::atomic::Ordering, pin::Pin};
+use core::sync::atomic::Ordering;
 
-use alloc::boxed::Box;
 /// This mod define the async controller, which implemented 
 /// 
 /// 
 
 use asyncc::*;
 use config::ASYNCC_ADDR;
-use mmrv::AllocatedFrame;
 
 use crate::frame_alloc;
+use crate::task::{current_process, fetch_task, local_executor, take_current_process};
+use crate::trap::{trap_handler, TRAP_CONTEXT_SIZE};
 
 
 /// The reasons why control flow turn to this function
@@ -41,7 +41,84 @@ pub unsafe extern "C" fn asyncc_entry() {
         // Exception/Interrupt: save all register in stack
         "ld a0, 0(sp)",
         "addi sp, sp, 8",
-        // "addi sp, sp, -8*34",
+        "addi sp, sp, -{trap_context_size}",
+        "sd x1, 1*8(sp)",
+        "sd x3, 3*8(sp)",
+        "sd x4, 4*8(sp)",
+        "sd x5, 5*8(sp)",
+        "sd x6, 6*8(sp)",
+        "sd x7, 7*8(sp)",
+        "sd x8, 8*8(sp)",
+        "sd x9, 9*8(sp)",
+        "sd x10, 10*8(sp)",
+        "sd x11, 11*8(sp)",
+        "sd x12, 12*8(sp)",
+        "sd x13, 13*8(sp)",
+        "sd x14, 14*8(sp)",
+        "sd x15, 15*8(sp)",
+        "sd x16, 16*8(sp)",
+        "sd x17, 17*8(sp)",
+        "sd x18, 18*8(sp)",
+        "sd x19, 19*8(sp)",
+        "sd x20, 20*8(sp)",
+        "sd x21, 21*8(sp)",
+        "sd x22, 22*8(sp)",
+        "sd x23, 23*8(sp)",
+        "sd x24, 24*8(sp)",
+        "sd x25, 25*8(sp)",
+        "sd x26, 26*8(sp)",
+        "sd x27, 27*8(sp)",
+        "sd x28, 28*8(sp)",
+        "sd x29, 29*8(sp)",
+        "sd x30, 30*8(sp)",
+        "sd x31, 31*8(sp)",
+        // the interrupted sp
+        "addi t0, sp, {trap_context_size}",
+        "sd t0, 2*8(sp)",
+        "csrr t0, sepc",
+        "sd t0, 32*8(sp)",
+        "csrr t0, sstatus",
+        "sd t0, 33*8(sp)",
+        // a0 => &mut TrapContext
+        "mv a0, sp",
+        "call {trap_handler}",
+        // restore the interrupted context
+        "ld t0, 32*8(sp)",
+        "csrw sepc, t0",
+        "ld t0, 33*8(sp)",
+        "csrw sstatus, t0",
+        "ld x1, 1*8(sp)",
+        "ld x3, 3*8(sp)",
+        "ld x4, 4*8(sp)",
+        "ld x5, 5*8(sp)",
+        "ld x6, 6*8(sp)",
+        "ld x7, 7*8(sp)",
+        "ld x8, 8*8(sp)",
+        "ld x9, 9*8(sp)",
+        "ld x10, 10*8(sp)",
+        "ld x11, 11*8(sp)",
+        "ld x12, 12*8(sp)",
+        "ld x13, 13*8(sp)",
+        "ld x14, 14*8(sp)",
+        "ld x15, 15*8(sp)",
+        "ld x16, 16*8(sp)",
+        "ld x17, 17*8(sp)",
+        "ld x18, 18*8(sp)",
+        "ld x19, 19*8(sp)",
+        "ld x20, 20*8(sp)",
+        "ld x21, 21*8(sp)",
+        "ld x22, 22*8(sp)",
+        "ld x23, 23*8(sp)",
+        "ld x24, 24*8(sp)",
+        "ld x25, 25*8(sp)",
+        "ld x26, 26*8(sp)",
+        "ld x27, 27*8(sp)",
+        "ld x28, 28*8(sp)",
+        "ld x29, 29*8(sp)",
+        "ld x30, 30*8(sp)",
+        "ld x31, 31*8(sp)",
+        "ld sp, 2*8(sp)",
+        "sret",
 
         "1:ld a0, 0(sp)",
         "addi sp, sp, 8",
@@ -49,8 +126,10 @@ pub unsafe extern "C" fn asyncc_entry() {
         "call {execute}",
         "j 0b",
         asyncc_addr = const ASYNCC_ADDR,
+        trap_context_size = const TRAP_CONTEXT_SIZE,
         handler = sym handler,
         execute = sym execute,
+        trap_handler = sym trap_handler,
         options(noreturn),
     );
 }
@@ -71,10 +150,8 @@ fn handler() -> Option<TaskRef> {
     let cause = asyncc::Asyncc::cause();
     let task = match cause {
         Cause::Finish => {
-            let executor = asyncc::Asyncc::get_executor();
             Asyncc::set_curr(None);
-            // TODO: check the priority bitmap in kernel `Executor`
-            executor.fetch()
+            Some(next_task())
         },
         Cause::Await => {
             let cur_task = asyncc::Asyncc::get_curr();
@@ -83,14 +160,17 @@ fn handler() -> Option<TaskRef> {
                 let task = unsafe { &*cur_task.as_ptr() };
                 match task.task_type {
                     // if the current task is a process, it must go to user process address space.
-                    TaskType::KernelProcess => {
+                    TaskType::KernelProcess | TaskType::Process => {
                         log::debug!("need to change executor, satp");
-                        let args = Asyncc::get_args2();
+                        let args = Asyncc::get_args8();
                         log::debug!("{:#X?}", args);
                         Asyncc::set_curr(None);
                         Asyncc::reset(args.a[1] as *const usize as _);
+                        // The `MsgBuf` registered by the process, 0 if there is none.
+                        Asyncc::set_msgbuf(args.a[3]);
                         let satp = args.a[0];
-                        let mut stack = AllocatedFrame::new(true).unwrap().start_address().value();
+                        // The stack is owned by the `Process`, see `ProcessTask`.
+                        let stack = args.a[2];
                         log::debug!("stack {:#X}", stack);
                         unsafe {
                             riscv::register::satp::write(satp);
@@ -107,43 +187,67 @@ fn handler() -> Option<TaskRef> {
                 };
             }
             Asyncc::set_curr(None);
-            // TODO: check the priority bitmap in kernel `Executor`
-            let executor = asyncc::Asyncc::get_executor();
-            executor.fetch()
+            Some(next_task())
         },
-        _ => {
-            todo!()
-        }
+        // Exceptions and interrupts are handled by `trap::trap_handler` with the context saved.
+        cause => unreachable!("{:?} in handler", cause),
     };
     // log::debug!("{:?}", task);
     task
 }
 
+/// Fetches the next task to run on this hart.
+///
+/// In a kernel process, the tasks are fetched from its `Executor`, and the hart goes back to
+/// the kernel once the process has exited or has nothing to run, so the kernel tasks never
+/// run in its address space. Back in the kernel, the process which the hart has left is
+/// handed over to `Process::leave`, since its stack is not in use any more.
+fn next_task() -> TaskRef {
+    let executor = Asyncc::get_executor();
+    if core::ptr::eq(executor, local_executor()) {
+        if let Some(process) = take_current_process() {
+            process.leave();
+        }
+    } else if let Some(process) = current_process().filter(|process| process.kernel_executor().is_some()) {
+        if !process.is_zombie() {
+            if let Some(task_ref) = executor.fetch() {
+                return task_ref;
+            }
+        }
+        drop(process);
+        unsafe { return_to_kernel() }
+    }
+    fetch_task()
+}
+
+/// Leaves the current process, e.g. it is killed in the trap handler, and goes back to the
+/// kernel `Executor` of this hart on the boot stack.
+///
+/// The boot stack is not in use, since it is left when switching to the process. The
+/// process is handed over to `Process::leave` on the boot stack.
+pub unsafe fn return_to_kernel() -> ! {
+    crate::mm::kernel_activate();
+    Asyncc::reset(local_executor());
+    Asyncc::set_msgbuf(0);
+    Asyncc::set_curr(None);
+    Asyncc::set_cause(Cause::Finish);
+    core::arch::asm!(
+        "mv sp, {stack}",
+        "j {entry}",
+        stack = in(reg) crate::boot_stack_top(crate::task::hart_id()),
+        entry = sym asyncc_entry,
+        options(noreturn),
+    );
+}
+
 /// This function need to be defined in kernel or user process. 
 #[no_mangle]
 pub fn execute(task_ref: Option<TaskRef>) {
     if let Some(task_ref) = task_ref {
         let executor = Asyncc::get_executor();
         executor.state.store(ExecutorState::Running as _, Ordering::Relaxed);
-        unsafe {
-            Asyncc::set_curr(Some(task_ref));
-            let waker = asyncc::from_task(task_ref);
-            let mut cx = Context::from_waker(&waker);
-            let task = Task::from_ref(task_ref);
-            task.state.store(TaskState::Running as _, Ordering::Relaxed);
-            let fut = &mut *task.fut.as_ptr();
-            let mut future = Pin::new_unchecked(fut.as_mut());
-            match future.as_mut().poll(&mut cx) {
-                Poll::Ready(_) => { 
-                    Asyncc::set_cause(crate::Cause::Finish);
-                },
-                Poll::Pending => {
-                    task.state.store(TaskState::Pending as _, Ordering::Relaxed);
-                    Asyncc::set_cause(crate::Cause::Await);
-                    let _task_ref = task.as_ref();
-                },
-            }
-        }
+        Asyncc::set_curr(Some(task_ref));
+        asyncc::execute(task_ref);
     } else {
         let executor = asyncc::Asyncc::get_executor();
         if executor.state.load(Ordering::Relaxed) == ExecutorState::Ready as _ {
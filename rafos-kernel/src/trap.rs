@@ -0,0 +1,98 @@
+/// This mod handles the exceptions and interrupts dispatched by `trampoline::asyncc_entry`.
+///
+/// The interrupted context is saved on the current stack as a [`TrapContext`],
+/// and it will be restored after the handler returns.
+
+use asyncc::{Cause, Exception, Interrupt};
+use mmrv::VirtAddr;
+use riscv::register::stval;
+
+use crate::{
+    mm::{do_handle_page_fault, VMFlags},
+    task::current_process,
+    timer,
+};
+
+/// The signal number of an invalid memory reference, the exit code of a process killed by
+/// a page fault is the negated one.
+const SIGSEGV: i32 = 11;
+
+/// General registers, `sepc` and `sstatus` of the interrupted context.
+///
+/// The layout must be consistent with `trampoline::asyncc_entry`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct TrapContext {
+    /// General registers `x0..x31`.
+    pub x: [usize; 32],
+    /// Supervisor exception program counter.
+    pub sepc: usize,
+    /// Supervisor status register.
+    pub sstatus: usize,
+}
+
+/// The size of [`TrapContext`] in bytes.
+pub const TRAP_CONTEXT_SIZE: usize = core::mem::size_of::<TrapContext>();
+
+/// Dispatches the exception or interrupt to the handler of its cause.
+#[link_section = ".text.trampoline"]
+#[no_mangle]
+pub fn trap_handler(cx: &mut TrapContext) {
+    match asyncc::Asyncc::cause() {
+        Cause::Intr(intr) => handle_interrupt(intr, cx),
+        Cause::Exception(exception) => handle_exception(exception, cx),
+        cause => panic!("{:?} must not go into the trap handler", cause),
+    }
+}
+
+fn handle_interrupt(intr: Interrupt, _cx: &mut TrapContext) {
+    match intr {
+        Interrupt::SupervisorTimer => {
+            timer::handle_timer_interrupt();
+        }
+        #[cfg(feature = "virtio-blk")]
+        Interrupt::SupervisorExternal => {
+            crate::device::plic::handle_external_interrupt(crate::task::hart_id());
+        }
+        #[cfg(not(feature = "virtio-blk"))]
+        Interrupt::SupervisorExternal => {
+            log::warn!("Unhandled external interrupt");
+        }
+        _ => panic!("Unsupported interrupt {:?}", intr),
+    }
+}
+
+fn handle_exception(exception: Exception, cx: &mut TrapContext) {
+    match exception {
+        Exception::UserEnvCall => {
+            // skip the `ecall` instruction
+            cx.sepc += 4;
+            // syscall id in `a7`, arguments in `a0..a5`
+            let ret = crate::syscall::syscall(
+                cx.x[17],
+                [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]],
+            );
+            cx.x[10] = ret as usize;
+        }
+        Exception::LoadPageFault | Exception::StorePageFault | Exception::InstPageFault => {
+            let va = VirtAddr::from(stval::read());
+            let flags = match exception {
+                Exception::LoadPageFault => VMFlags::READ,
+                Exception::StorePageFault => VMFlags::WRITE,
+                _ => VMFlags::EXEC,
+            };
+            let Some(process) = current_process() else {
+                panic!("{:?} at {:#X}, bad addr = {:?}: no process", exception, cx.sepc, va);
+            };
+            let result = do_handle_page_fault(&mut process.mm.lock(), va, flags);
+            if let Err(err) = result {
+                // Only the faulting process is killed, the kernel goes on.
+                log::warn!("{:?} at {:#X}, bad addr = {:?}: {:?}, pid = {}", exception, cx.sepc, va, err, process.pid.0);
+                process.exit(-SIGSEGV);
+                drop(process);
+                unsafe { crate::trampoline::return_to_kernel() }
+            }
+        }
+        _ => panic!("Unsupported exception {:?} at {:#X}, stval = {:#X}", exception, cx.sepc, stval::read()),
+    }
+}
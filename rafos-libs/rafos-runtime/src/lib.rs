@@ -7,10 +7,14 @@ mod heap;
 extern crate alloc;
 use core::future::Future;
 use alloc::boxed::Box;
-use executor::{Executor, TaskType, TaskRef, execute};
+use asyncc::{Asyncc, Executor, MsgBuf, TaskType, TaskRef, execute};
 core::arch::global_asm!(include_str!("info.asm"));
 
-static mut EXECUTOR: Executor = Executor::new();
+static EXECUTOR: Executor = Executor::new();
+
+/// The messages posted to this process, registered when polling for the first time.
+static MSGBUF: MsgBuf = MsgBuf::new();
+static MSGBUF_REGISTERED: spin::Once = spin::Once::new();
 
 pub mod lang_item {
     ///
@@ -29,27 +33,38 @@ pub mod lang_item {
 }
 
 
+/// Spawns a detached task, whose `TaskRef` is valid until it finishes.
 #[no_mangle]
 pub fn spawn(fut: Box<dyn Future<Output = i32> + 'static + Send + Sync>, priority: u32, task_type: TaskType) -> TaskRef {
-    let executor = unsafe { &mut EXECUTOR };
-    executor.spawn(fut, priority, task_type)
+    EXECUTOR.spawn(fut, priority, task_type).task_ref()
 }
 
 #[no_mangle]
 pub fn poll_future() {
-    let executor = unsafe { &mut EXECUTOR };
-    while let Some(task_ref) = executor.fetch(0) {
-        if let Some(task_ref) = execute(task_ref) {
-            if (unsafe { &*task_ref.as_ptr() }).task_type == TaskType::KernelSche {
-                executor.wake_task_from_ref(task_ref);
+    MSGBUF_REGISTERED.call_once(|| {
+        let msgbuf = &MSGBUF as *const MsgBuf as usize;
+        if syscall::sys_register_msg_buf(msgbuf) == 0 {
+            Asyncc::set_msgbuf(msgbuf);
+        }
+    });
+    loop {
+        while let Some(task_ref) = EXECUTOR.fetch() {
+            if let Some(task_ref) = execute(task_ref) {
+                if (unsafe { &*task_ref.as_ptr() }).task_type == TaskType::KernelSche {
+                    asyncc::wake_task(task_ref);
+                }
             }
         }
+        // The messages posted while polling may wake more coroutines.
+        if Asyncc::dispatch_messages() == 0 {
+            break;
+        }
     }
 }
 
 #[no_mangle]
 pub fn wake_task(task_ref: TaskRef) {
-    executor::wake_task(task_ref);
+    asyncc::wake_task(task_ref);
 }
 
 extern "C" {
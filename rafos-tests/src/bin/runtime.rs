@@ -4,7 +4,7 @@
 use core::future::Future;
 use core::pin::Pin;
 use core::task::Context;
-use executor::{TaskType, TaskRef};
+use asyncc::{TaskType, TaskRef};
 use std::time::Instant;
 extern crate sys_info;
 
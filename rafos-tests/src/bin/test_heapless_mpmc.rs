@@ -7,7 +7,7 @@ const N: u32 = 2;
 
 static Q: MpMcQueue<u32, 2> = MpMcQueue::new();
 
-use executor::TaskRef;
+use asyncc::TaskRef;
 fn main() {
 
     println!("{}", core::mem::size_of::<MpMcQueue<TaskRef, 32>>());